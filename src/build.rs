@@ -0,0 +1,142 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crate::{Error, LineIndex, Mage, Severity, Stage, Type};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BuildResult {
+    pub path: PathBuf,
+    pub error: Option<Error>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BuildSummary {
+    pub results: Vec<BuildResult>,
+}
+
+impl BuildSummary {
+    pub fn succeeded(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.error.is_none())
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+}
+
+impl fmt::Display for BuildSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            if let Some(error) = &result.error {
+                writeln!(f, "{}: {}", result.path.display(), error)?;
+            }
+        }
+
+        write!(
+            f,
+            "{} succeeded, {} failed",
+            self.succeeded(),
+            self.failed()
+        )
+    }
+}
+
+/// Runs every `.mage` file below `directory` through the pipeline up to
+/// `stage`, spreading the files over one parser per available core. A file
+/// whose diagnostics contain an error fails with the first of them.
+pub fn build_directory(directory: &Path, stage: &Stage) -> Result<BuildSummary, Error> {
+    let mut paths = Vec::new();
+    collect_sources(directory, &mut paths)?;
+    paths.sort();
+
+    let workers = thread::available_parallelism()
+        .map(|workers| workers.get())
+        .unwrap_or(1);
+    let chunk_size = paths.len().div_ceil(workers).max(1);
+
+    let results = thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || build_files(chunk, stage)))
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    Ok(BuildSummary { results })
+}
+
+fn build_files(paths: &[PathBuf], stage: &Stage) -> Vec<BuildResult> {
+    let mut mage = Mage::new();
+
+    paths
+        .iter()
+        .map(|path| {
+            let result = match &mut mage {
+                Ok(mage) => fs::read_to_string(path)
+                    .map_err(|error| {
+                        Error::MageError(format!(
+                            "Error: Unable to read {}: {}.",
+                            path.display(),
+                            error
+                        ))
+                    })
+                    .and_then(|text| build_file(mage, stage, &text)),
+                Err(error) => Err(error.clone()),
+            };
+
+            BuildResult {
+                path: path.clone(),
+                error: result.err(),
+            }
+        })
+        .collect()
+}
+
+fn build_file(mage: &mut Mage, stage: &Stage, text: &str) -> Result<(), Error> {
+    if let Type::Diagnostics(diagnostics) = mage.process(stage, text)?
+        && let Some(diagnostic) = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        return Err(Error::ValidateError(
+            diagnostic.render(&LineIndex::new(text)),
+        ));
+    }
+
+    Ok(())
+}
+
+fn collect_sources(directory: &Path, paths: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let entries = fs::read_dir(directory).map_err(|error| {
+        Error::MageError(format!(
+            "Error: Unable to read directory {}: {}.",
+            directory.display(),
+            error
+        ))
+    })?;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect_sources(&path, paths)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "mage")
+        {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
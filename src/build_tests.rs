@@ -0,0 +1,39 @@
+use std::{env, fs};
+
+use crate::{Stage, build_directory};
+
+#[test]
+fn test_build_directory() {
+    let directory = env::temp_dir().join(format!("mage-build-{}", std::process::id()));
+    fs::create_dir_all(directory.join("nested")).unwrap();
+
+    fs::write(directory.join("good.mage"), "a : 0d1 + 0d2").unwrap();
+    fs::write(directory.join("nested").join("bad.mage"), "a : 0d1 )").unwrap();
+    fs::write(directory.join("notes.txt"), "not mage )").unwrap();
+
+    let summary = build_directory(&directory, &Stage::Flatten).unwrap();
+
+    assert_eq!(summary.succeeded(), 1);
+    assert_eq!(summary.failed(), 1);
+    assert_eq!(
+        summary.to_string(),
+        format!(
+            "{}: Error: Cannot process node of unknown type ERROR.\n1 succeeded, 1 failed",
+            directory.join("nested").join("bad.mage").display()
+        )
+    );
+
+    let summary = build_directory(&directory, &Stage::Validate).unwrap();
+
+    assert_eq!(summary.succeeded(), 1);
+    assert_eq!(summary.failed(), 1);
+    assert_eq!(
+        summary.to_string(),
+        format!(
+            "{}: 1:9: Error: Unexpected `)`.\n1 succeeded, 1 failed",
+            directory.join("nested").join("bad.mage").display()
+        )
+    );
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -1,14 +1,27 @@
+use std::path::PathBuf;
+
 use clap::{Args, Parser, Subcommand, ValueEnum};
 
+use crate::{DEFAULT_MAX_ERRORS, Diagnostic, Severity};
+
 #[derive(Debug, Clone, ValueEnum)]
 pub enum Output {
     Text,
     Json,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy, Default, ValueEnum)]
+pub enum IntWidth {
+    I32,
+    #[default]
+    I64,
+}
+
 #[derive(Debug, Clone, ValueEnum)]
 pub enum Stage {
+    Validate,
     Flatten,
+    #[cfg(feature = "jit")]
     Compile,
 }
 
@@ -19,23 +32,176 @@ pub struct Cli {
     /// output
     #[arg(long, default_value = "text")]
     pub output: Output,
+    /// integer width
+    #[arg(long, default_value = "i64")]
+    pub int_width: IntWidth,
+    /// diagnostics reported per file before the rest are suppressed
+    #[arg(long, default_value_t = DEFAULT_MAX_ERRORS)]
+    pub max_errors: usize,
 }
 
 #[derive(Debug, Clone, Subcommand)]
 pub enum Command {
     /// Run
     Run(Run),
+    /// Validate without emitting output
+    Check(Check),
+    /// Process every file of a directory
+    Build(Build),
+    /// Rewrite a file in canonical form
+    Fmt(Fmt),
+    /// Describe an error code
+    Explain(Explain),
     /// Print environment
     Environment,
     /// Run language server
+    #[cfg(feature = "lsp")]
     LanguageServer,
 }
 
 #[derive(Debug, Clone, Args)]
 pub struct Run {
-    /// path
+    /// path, `file://` URI, or `-` for stdin (the default); use `./-` for a file named `-`
     pub path: Option<String>,
     /// stage
     #[arg(long, default_value = "flatten")]
     pub stage: Stage,
+    /// print the parse tree to stderr when processing fails
+    #[arg(long)]
+    pub emit_tree: bool,
+    /// print how long each stage took to stderr
+    #[arg(long)]
+    pub time: bool,
+    /// run again whenever the file changes
+    #[arg(long, requires = "path")]
+    pub watch: bool,
+    /// skip validating the program before later stages, for trusted input
+    #[arg(long, conflicts_with = "strict")]
+    pub no_validate: bool,
+    /// fail on warnings as well as errors
+    #[arg(long)]
+    pub strict: bool,
+}
+
+/// Where `run` reads its program from.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Input {
+    /// Standard input, one program per line.
+    Stdin,
+    File(PathBuf),
+}
+
+impl Run {
+    pub fn input(&self) -> Input {
+        match self.path.as_deref() {
+            None | Some("-") => Input::Stdin,
+            Some(path) => Input::File(file_uri_path(path).unwrap_or_else(|| PathBuf::from(path))),
+        }
+    }
+
+    /// Whether `diagnostics` stop the run: any error does, and so does any
+    /// warning under `--strict`.
+    pub fn fails(&self, diagnostics: &[Diagnostic]) -> bool {
+        diagnostics
+            .iter()
+            .any(|diagnostic| match diagnostic.severity {
+                Severity::Error => true,
+                Severity::Warning => self.strict,
+                Severity::Information | Severity::Hint => false,
+            })
+    }
+}
+
+/// Path of a `file:` URI such as `file:///tmp/a%20b.mage`, with percent
+/// escapes decoded, or `None` when `uri` is not one. The host may be empty or
+/// `localhost`; any other host is kept as a `//host/...` network path.
+fn file_uri_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri
+        .get(..7)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("file://"))
+        .map(|_| &uri[7..])?;
+
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+
+    let (host, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, "/"),
+    };
+
+    let mut bytes = Vec::with_capacity(path.len());
+
+    if !host.is_empty() && !host.eq_ignore_ascii_case("localhost") {
+        bytes.extend_from_slice(b"//");
+        bytes.extend_from_slice(host.as_bytes());
+    }
+
+    // `/C:/...` on Windows names a drive, not a directory under the root.
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if cfg!(windows) && drive.is_ascii_alphabetic() => &path[1..],
+        _ => path,
+    };
+
+    let mut rest = path.as_bytes();
+
+    while let [first, tail @ ..] = rest {
+        let escaped = match tail {
+            [high, low, ..] if *first == b'%' => (*high as char)
+                .to_digit(16)
+                .zip((*low as char).to_digit(16)),
+            _ => None,
+        };
+
+        match escaped {
+            Some((high, low)) => {
+                bytes.push((high * 16 + low) as u8);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(*first);
+                rest = tail;
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    let path = {
+        use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+        PathBuf::from(OsString::from_vec(bytes))
+    };
+
+    #[cfg(not(unix))]
+    let path = PathBuf::from(String::from_utf8_lossy(&bytes).into_owned());
+
+    Some(path)
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct Check {
+    /// path
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct Build {
+    /// directory
+    pub path: String,
+    /// stage
+    #[arg(long, default_value = "flatten")]
+    pub stage: Stage,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct Fmt {
+    /// path
+    pub path: String,
+    /// fail instead of rewriting when the file is not formatted
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct Explain {
+    /// error code, e.g. E0004
+    pub code: String,
 }
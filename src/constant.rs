@@ -0,0 +1,302 @@
+use std::collections::{HashMap, HashSet};
+
+use tree_sitter::Node;
+
+use crate::{Error, IntWidth, NodeKinds, is_number_literal};
+
+/// Constants bound in one source. A name bound more than once, or bound as a
+/// variable, has no known value.
+pub(crate) struct Scope<'a, 'tree> {
+    constants: Vec<(&'a str, Option<Node<'tree>>)>,
+    parent: Option<&'a Scope<'a, 'tree>>,
+}
+
+impl<'a, 'tree> Scope<'a, 'tree> {
+    pub(crate) fn new(
+        node_kinds: &NodeKinds,
+        source: Node<'tree>,
+        code: &'a str,
+        parent: Option<&'a Scope<'a, 'tree>>,
+    ) -> Self {
+        let mut scope = Self {
+            constants: Vec::new(),
+            parent,
+        };
+
+        for child in source.named_children(&mut source.walk()) {
+            scope.collect(node_kinds, child, code);
+        }
+
+        scope
+    }
+
+    fn collect(&mut self, node_kinds: &NodeKinds, node: Node<'tree>, code: &'a str) {
+        if node.kind_id() == node_kinds.source {
+            return;
+        }
+
+        if node.kind_id() == node_kinds.assign
+            && let Some(target) = node.named_child(0)
+            && target.kind_id() == node_kinds.identifier
+        {
+            let name = &code[target.byte_range()];
+            let value = node
+                .named_child(1)
+                .filter(|operator| operator.kind_id() == node_kinds.constant)
+                .and(node.named_child(2));
+
+            match self.constants.iter_mut().find(|(other, _)| *other == name) {
+                Some((_, known)) => *known = None,
+                None => self.constants.push((name, value)),
+            }
+        }
+
+        for child in node.named_children(&mut node.walk()) {
+            self.collect(node_kinds, child, code);
+        }
+    }
+
+    /// Value of the constant `name` and the scope it was bound in.
+    pub(crate) fn lookup(&self, name: &str) -> Option<(Node<'tree>, &Self)> {
+        match self.constants.iter().find(|(other, _)| *other == name) {
+            Some((_, value)) => value.map(|value| (value, self)),
+            None => self.parent?.lookup(name),
+        }
+    }
+}
+
+/// Finds the first division or modulo whose divisor is provably zero, either
+/// a literal or arithmetic over constants that evaluates to zero. Anything
+/// that cannot be evaluated, such as a variable or a call, is assumed to be
+/// non-zero. Sources whose span is in `skip` are not searched.
+pub(crate) fn division_by_zero(
+    node_kinds: &NodeKinds,
+    root: Node,
+    code: &str,
+    int_width: IntWidth,
+    skip: &HashSet<(usize, usize)>,
+) -> Option<Error> {
+    let scope = Scope::new(node_kinds, root, code, None);
+    let mut known = HashMap::new();
+
+    find_division_by_zero(node_kinds, root, code, int_width, &scope, skip, &mut known)
+}
+
+/// Top-level constant definitions whose value is known, as the span of the
+/// definition, the name it binds and its value.
+#[cfg(feature = "lsp")]
+pub(crate) fn constant_values<'a>(
+    node_kinds: &NodeKinds,
+    root: Node,
+    code: &'a str,
+    int_width: IntWidth,
+) -> Vec<((usize, usize), &'a str, i64)> {
+    let scope = Scope::new(node_kinds, root, code, None);
+    let mut known = HashMap::new();
+    let mut values = Vec::new();
+
+    for child in root.named_children(&mut root.walk()) {
+        if child.kind_id() == node_kinds.assign
+            && let Some(target) = child.named_child(0)
+            && target.kind_id() == node_kinds.identifier
+            && child
+                .named_child(1)
+                .is_some_and(|operator| operator.kind_id() == node_kinds.constant)
+            && let Some(value) = evaluate(
+                node_kinds,
+                child,
+                code,
+                int_width,
+                &scope,
+                &mut Vec::new(),
+                &mut known,
+            )
+        {
+            values.push((
+                (child.start_byte(), child.end_byte()),
+                &code[target.byte_range()],
+                value,
+            ));
+        }
+    }
+
+    values
+}
+
+fn find_division_by_zero(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+    int_width: IntWidth,
+    scope: &Scope,
+    skip: &HashSet<(usize, usize)>,
+    known: &mut HashMap<usize, Option<i64>>,
+) -> Option<Error> {
+    for child in node.named_children(&mut node.walk()) {
+        let error = if child.kind_id() == node_kinds.source {
+            if skip.contains(&(child.start_byte(), child.end_byte())) {
+                continue;
+            }
+
+            let nested = Scope::new(node_kinds, child, code, Some(scope));
+
+            find_division_by_zero(node_kinds, child, code, int_width, &nested, skip, known)
+        } else {
+            find_division_by_zero(node_kinds, child, code, int_width, scope, skip, known)
+        };
+
+        if error.is_some() {
+            return error;
+        }
+    }
+
+    if node.kind_id() == node_kinds.multiplicative
+        && let (Some(operator), Some(divisor)) = (node.named_child(1), node.named_child(2))
+        && (operator.kind_id() == node_kinds.divide || operator.kind_id() == node_kinds.modulo)
+        && evaluate(
+            node_kinds,
+            divisor,
+            code,
+            int_width,
+            scope,
+            &mut Vec::new(),
+            known,
+        ) == Some(0)
+    {
+        return Some(Error::DivisionByZero {
+            span: (node.start_byte(), node.end_byte()),
+        });
+    }
+
+    None
+}
+
+/// Value of a constant expression, or `None` when it is not known at compile
+/// time. `visiting` holds the names being evaluated, so that circular
+/// definitions have no value instead of recursing forever. `known` holds the
+/// value of each expression bound to a constant by node id, so that every
+/// definition is evaluated once however often it is used. Literals that do
+/// not fit in `int_width` have no value, and arithmetic wraps at it.
+fn evaluate<'a>(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &'a str,
+    int_width: IntWidth,
+    scope: &Scope,
+    visiting: &mut Vec<&'a str>,
+    known: &mut HashMap<usize, Option<i64>>,
+) -> Option<i64> {
+    let kind = node.kind_id();
+    let text = &code[node.byte_range()];
+
+    if [
+        node_kinds.binary,
+        node_kinds.octal,
+        node_kinds.decimal,
+        node_kinds.hex,
+    ]
+    .contains(&kind)
+        || (kind == node_kinds.identifier && is_number_literal(text))
+    {
+        return int_width.parse(text).ok();
+    }
+
+    if kind == node_kinds.identifier {
+        if visiting.contains(&text) {
+            return None;
+        }
+
+        let (value, defined) = scope.lookup(text)?;
+
+        if let Some(result) = known.get(&value.id()) {
+            return *result;
+        }
+
+        visiting.push(text);
+        let result = evaluate(node_kinds, value, code, int_width, defined, visiting, known);
+        visiting.pop();
+
+        known.insert(value.id(), result);
+
+        return result;
+    }
+
+    if kind == node_kinds.parenthesize {
+        return evaluate(
+            node_kinds,
+            node.named_child(0)?,
+            code,
+            int_width,
+            scope,
+            visiting,
+            known,
+        );
+    }
+
+    // The value of `a : b : 0d5` is the value bound to `b`.
+    if kind == node_kinds.assign {
+        return evaluate(
+            node_kinds,
+            node.named_child(2)?,
+            code,
+            int_width,
+            scope,
+            visiting,
+            known,
+        );
+    }
+
+    if kind != node_kinds.additive && kind != node_kinds.multiplicative {
+        return None;
+    }
+
+    // `-x` and `+x`. A negated literal is parsed with its sign, since the
+    // magnitude of the smallest value does not fit on its own.
+    if node.named_child_count() == 2 {
+        let operator = node.named_child(0)?.kind_id();
+        let operand = node.named_child(1)?;
+
+        if operator == node_kinds.add {
+            return evaluate(node_kinds, operand, code, int_width, scope, visiting, known);
+        }
+
+        if is_number_literal(&code[operand.byte_range()]) {
+            return int_width.parse_negated(&code[operand.byte_range()]).ok();
+        }
+
+        let value = evaluate(node_kinds, operand, code, int_width, scope, visiting, known)?;
+
+        return Some(int_width.wrap(value.wrapping_neg()));
+    }
+
+    let one = evaluate(
+        node_kinds,
+        node.named_child(0)?,
+        code,
+        int_width,
+        scope,
+        visiting,
+        known,
+    )?;
+    let operator = node.named_child(1)?.kind_id();
+    let two = evaluate(
+        node_kinds,
+        node.named_child(2)?,
+        code,
+        int_width,
+        scope,
+        visiting,
+        known,
+    )?;
+
+    let value = match operator {
+        operator if operator == node_kinds.add => one.wrapping_add(two),
+        operator if operator == node_kinds.subtract => one.wrapping_sub(two),
+        operator if operator == node_kinds.multiply => one.wrapping_mul(two),
+        operator if operator == node_kinds.divide && two != 0 => one.wrapping_div(two),
+        operator if operator == node_kinds.modulo && two != 0 => one.wrapping_rem(two),
+        _ => return None,
+    };
+
+    Some(int_width.wrap(value))
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Edit, Error, ErrorCode, LineIndex};
+
+/// Diagnostics reported for one document unless configured otherwise.
+pub const DEFAULT_MAX_ERRORS: usize = 100;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: (usize, usize),
+    pub code: Option<ErrorCode>,
+    pub fixes: Vec<Fix>,
+}
+
+/// Edits that together resolve a diagnostic, applied all at once.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Fix {
+    pub title: String,
+    pub edits: Vec<Edit>,
+}
+
+impl Diagnostic {
+    pub fn error(message: String, span: (usize, usize)) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+            span,
+            code: None,
+            fixes: Vec::new(),
+        }
+    }
+
+    pub fn warning(message: String, span: (usize, usize)) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+            span,
+            code: None,
+            fixes: Vec::new(),
+        }
+    }
+
+    pub fn information(message: String, span: (usize, usize)) -> Self {
+        Self {
+            severity: Severity::Information,
+            ..Self::error(message, span)
+        }
+    }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_fix(self, title: String, span: (usize, usize), replacement: String) -> Self {
+        self.with_edits(title, vec![Edit { span, replacement }])
+    }
+
+    pub fn with_edits(mut self, title: String, edits: Vec<Edit>) -> Self {
+        self.fixes.push(Fix { title, edits });
+        self
+    }
+
+    /// Converts a pipeline error, falling back to `span` when the error does
+    /// not carry a location of its own.
+    pub fn from_error(error: &Error, span: (usize, usize)) -> Self {
+        Self {
+            code: error.code(),
+            ..Self::error(error.to_string(), error.span().unwrap_or(span))
+        }
+    }
+
+    pub fn render(&self, line_index: &LineIndex) -> String {
+        let (line, column) = line_index.position(self.span.0);
+
+        format!("{}:{}: {}", line + 1, column + 1, self)
+    }
+}
+
+/// Keeps the first `max` diagnostics and notes how many were dropped, so a
+/// badly broken file does not flood the output.
+pub fn limit_diagnostics(mut diagnostics: Vec<Diagnostic>, max: usize) -> Vec<Diagnostic> {
+    if diagnostics.len() <= max {
+        return diagnostics;
+    }
+
+    let span = diagnostics[max].span;
+    let suppressed = diagnostics.len() - max;
+
+    diagnostics.truncate(max);
+    diagnostics.push(Diagnostic::information(
+        format!("Information: {} more diagnostics suppressed.", suppressed),
+        span,
+    ));
+
+    diagnostics
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
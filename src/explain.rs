@@ -0,0 +1,161 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+/// Stable identifiers for the problems validation reports. Codes never change
+/// meaning once published, so new problems get new codes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorCode {
+    UnexpectedSyntax,
+    MissingSyntax,
+    DefinitionWithoutExpression,
+    InvalidNumber,
+    NumberOutOfRange,
+    MixedDefinitionChain,
+    DivisionByZero,
+    UnsupportedNode,
+    TypeMismatch,
+    InvalidEscape,
+    UnusedVariable,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 11] = [
+        ErrorCode::UnexpectedSyntax,
+        ErrorCode::MissingSyntax,
+        ErrorCode::DefinitionWithoutExpression,
+        ErrorCode::InvalidNumber,
+        ErrorCode::NumberOutOfRange,
+        ErrorCode::MixedDefinitionChain,
+        ErrorCode::DivisionByZero,
+        ErrorCode::UnsupportedNode,
+        ErrorCode::TypeMismatch,
+        ErrorCode::InvalidEscape,
+        ErrorCode::UnusedVariable,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedSyntax => "E0001",
+            ErrorCode::MissingSyntax => "E0002",
+            ErrorCode::DefinitionWithoutExpression => "E0003",
+            ErrorCode::InvalidNumber => "E0004",
+            ErrorCode::NumberOutOfRange => "E0005",
+            ErrorCode::MixedDefinitionChain => "E0006",
+            ErrorCode::DivisionByZero => "E0007",
+            ErrorCode::UnsupportedNode => "E0008",
+            ErrorCode::TypeMismatch => "E0009",
+            ErrorCode::InvalidEscape => "E0010",
+            ErrorCode::UnusedVariable => "W0001",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|error_code| error_code.code().eq_ignore_ascii_case(code))
+    }
+
+    /// Longer description of the problem, followed by an example and its fix.
+    pub fn explain(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedSyntax => {
+                "The parser found text that does not fit anywhere in the program, such as a \
+                 closing parenthesis without an opening one.\n\n\
+                 a : 0d1 )\n\n\
+                 Remove the stray text or add what it belongs to:\n\n\
+                 a : (0d1)"
+            }
+            ErrorCode::MissingSyntax => {
+                "An expression ended before it was complete, so the parser had to assume a \
+                 missing piece, usually an operand after an operator.\n\n\
+                 a : 0d1 +\n\n\
+                 Complete the expression:\n\n\
+                 a : 0d1 + 0d2"
+            }
+            ErrorCode::DefinitionWithoutExpression => {
+                "A name is followed by `:` or `=` but nothing is bound to it.\n\n\
+                 a :\n\n\
+                 Give the definition a value:\n\n\
+                 a : 0d1"
+            }
+            ErrorCode::InvalidNumber => {
+                "Mage integers use the radix prefixes 0b (binary), 0o (octal), 0d (decimal) \
+                 or 0x (hexadecimal), followed by digits of that radix. Digits may be grouped \
+                 with single underscores between them. Words starting with a digit are never \
+                 valid names.\n\n\
+                 a : 42\n\
+                 b : 0b12\n\n\
+                 Add a prefix, or pick the radix the digits were meant in:\n\n\
+                 a : 0d42\n\
+                 b : 0d12"
+            }
+            ErrorCode::NumberOutOfRange => {
+                "The value of a number literal does not fit in the integer width chosen \
+                 with --int-width.\n\n\
+                 a : 0x1_0000_0000\n\n\
+                 Use a smaller value, or a wider integer with --int-width i64."
+            }
+            ErrorCode::MixedDefinitionChain => {
+                "A chain of definitions binds every name to the same value, so all of them \
+                 have to be constants (`:`) or all of them variables (`=`).\n\n\
+                 a : b = 0d5\n\n\
+                 Use the same operator throughout:\n\n\
+                 a : b : 0d5"
+            }
+            ErrorCode::DivisionByZero => {
+                "The divisor of a division or modulo is zero, either written as a literal or \
+                 computed from constants.\n\n\
+                 x : 0d0\n\
+                 y : 0d10 / x\n\n\
+                 Divide by a value that cannot be zero, or make the divisor a variable that is \
+                 checked before dividing."
+            }
+            ErrorCode::UnsupportedNode => {
+                "The program contains a construct that the grammar accepts but the compiler \
+                 does not handle yet. Rewrite the expression with the supported operators, or \
+                 report it if it should work."
+            }
+            ErrorCode::TypeMismatch => {
+                "An operator is applied to values it does not work on. Arithmetic, logic and \
+                 ordering need numbers, except that `+` also joins two strings and `<` and \
+                 friends also compare two strings. Both sides must have the same type, and only \
+                 names and sources can be called.\n\n\
+                 a : \"a\" * 0d2\n\n\
+                 Use values of the right type:\n\n\
+                 a : \"a\" + \"a\""
+            }
+            ErrorCode::InvalidEscape => {
+                "A backslash in a string starts an escape that does not spell a character: \
+                 `\\x` needs two hexadecimal digits, `\\u` four, or any number between `{` and a \
+                 closing `}`, and the code point has to be a valid character, so \
+                 surrogates such as `\\uD800` are rejected.\n\n\
+                 a : \"\\u{41\"\n\n\
+                 Complete the escape:\n\n\
+                 a : \"\\u{41}\""
+            }
+            ErrorCode::UnusedVariable => {
+                "A name is defined but never used in its source or any source nested in it.\n\n\
+                 a : 0d1\n\
+                 0d2 => print\n\n\
+                 Use the name, or remove the definition:\n\n\
+                 0d2 => print"
+            }
+        }
+    }
+}
+
+/// Codes serialize as their string form, e.g. `"E0004"`, so tools can key off
+/// them.
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+
+        ErrorCode::from_code(&code)
+            .ok_or_else(|| de::Error::custom(format!("unknown error code `{}`", code)))
+    }
+}
@@ -0,0 +1,39 @@
+use crate::ErrorCode;
+
+#[test]
+fn test_codes_round_trip() {
+    for error_code in ErrorCode::ALL {
+        assert_eq!(ErrorCode::from_code(error_code.code()), Some(error_code));
+    }
+
+    assert_eq!(
+        ErrorCode::from_code("e0004"),
+        Some(ErrorCode::InvalidNumber)
+    );
+    assert_eq!(ErrorCode::from_code("E9999"), None);
+}
+
+#[test]
+fn test_explain_invalid_number() {
+    let explanation = ErrorCode::InvalidNumber.explain();
+
+    for prefix in ["0b", "0o", "0d", "0x"] {
+        assert!(explanation.contains(prefix), "{} is not mentioned", prefix);
+    }
+}
+
+#[test]
+fn test_codes_are_stable() {
+    assert_eq!(ErrorCode::InvalidNumber.code(), "E0004");
+    assert_eq!(ErrorCode::UnsupportedNode.code(), "E0008");
+
+    for (index, error_code) in ErrorCode::ALL.iter().enumerate() {
+        assert!(
+            ErrorCode::ALL[..index]
+                .iter()
+                .all(|other| other.code() != error_code.code()),
+            "{} is used twice",
+            error_code.code()
+        );
+    }
+}
@@ -1,132 +1,323 @@
-use serde::{Deserialize, Serialize};
-use tree_sitter::{Node, Tree};
+use std::collections::{HashMap, HashSet};
 
-use crate::{Error, NodeKinds};
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Range, Tree};
+
+use crate::{
+    Error, IntWidth, NodeKinds, decode_string, decode_string_at, division_by_zero,
+    is_malformed_number, is_number_literal, type_error,
+};
+
+/// Flattens `tree` depth-first over named children, in source order. A source
+/// is appended to `FlatRoot::sources` once it is complete, so nested sources
+/// come before the source containing them. Numbers, strings and identifiers
+/// are deduplicated in the order they are first met. The result only depends
+/// on the input text, which keeps serialized roots stable. Number literals
+/// and constant arithmetic are checked at `int_width`.
+pub fn flatten_tree(
+    node_kinds: &NodeKinds,
+    tree: &Tree,
+    code: &str,
+    int_width: IntWidth,
+) -> Result<FlatRoot, Error> {
+    let mut root_builder = FlatRootBuilder::new(int_width);
 
-pub fn flatten_tree(node_kinds: &NodeKinds, tree: Tree, code: &str) -> Result<FlatRoot, Error> {
-    let mut root_builder = FlatRootBuilder::new();
+    flatten_with(&mut root_builder, node_kinds, tree, code)?;
 
-    flatten_node(&mut root_builder, node_kinds, tree.root_node(), code)?;
+    root_builder.root()
+}
 
-    Ok(root_builder.root()?)
+/// Result of `flatten_incremental`, with the number of sources copied from
+/// the previous root rather than flattened again.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IncrementalFlatten {
+    pub root: FlatRoot,
+    pub reused: usize,
 }
 
-fn flatten_node<Builder: FlatBuilder>(
-    builder: &mut Builder,
+/// Flattens `tree`, which was parsed from `code` with `old_tree` after the
+/// edits were applied to `old_tree`, as `Mage::parse_edited` does. Sources
+/// that no edit touched and that are outside `Tree::changed_ranges` are
+/// copied from `previous`, the root `old_tree` flattened to, instead of
+/// being flattened again, and are not checked again when they only use names
+/// bound inside them. It fails where `flatten_tree` fails and otherwise has
+/// the same sources, expressions and identifiers, but the numbers and strings
+/// may differ, and with them the indexes referring to equal literals. Copied
+/// sources only send the literals their expressions use, so a literal no
+/// expression uses, such as `"q"` in `{ y : 0xFF "q" }`, is left out, those
+/// of a source no expression uses come after the others, and a number or
+/// string spelled differently in an unchanged source may keep the spelling
+/// met first in `previous`.
+pub fn flatten_incremental(
     node_kinds: &NodeKinds,
-    node: Node,
+    previous: &FlatRoot,
+    old_tree: &Tree,
+    tree: &Tree,
     code: &str,
-) -> Result<(), Error> {
-    let node_kind = node.kind_id();
+    int_width: IntWidth,
+) -> Result<IncrementalFlatten, Error> {
+    let changed: Vec<Range> = old_tree.changed_ranges(tree).collect();
 
-    let node_text = node.utf8_text(code.as_bytes()).map_err(|error| {
-        Error::FlattenError(format!("Error: Failed to extract UTF-8 text: {}.", error))
-    })?;
+    let mut sources = Vec::new();
+    collect_previous_sources(node_kinds, old_tree.root_node(), &changed, &mut sources);
 
-    match node_kind {
-        kind if kind == node_kinds.source_file || kind == node_kinds.source => {
-            let mut source_builder = FlatSourceBuilder::new(builder);
+    let reusable = sources
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, _, reusable))| *reusable)
+        .map(|(index, (span, first, _))| (*span, (*first, index)))
+        .collect();
 
-            for child in node.named_children(&mut node.walk()) {
-                flatten_node(&mut source_builder, node_kinds, child, code)?;
-            }
+    let mut root_builder = FlatRootBuilder::new(int_width);
+    root_builder.reuse = Some(Reuse::new(previous, reusable));
 
-            let source = source_builder.source()?;
+    flatten_with(&mut root_builder, node_kinds, tree, code)?;
 
-            builder.take_source(source)?;
-        }
-        kind if kind == node_kinds.member => {
-            let mut binary_builder = FlatBinaryBuilder::new(builder);
+    let reused = root_builder.reuse.as_ref().map_or(0, |reuse| reuse.reused);
 
-            for child in node.named_children(&mut node.walk()) {
-                flatten_node(&mut binary_builder, node_kinds, child, code)?;
-            }
+    Ok(IncrementalFlatten {
+        root: root_builder.root()?,
+        reused,
+    })
+}
 
-            let binary = binary_builder.binary()?;
+/// Sources of the previous tree in the order they were flattened, each with
+/// the index of the first source nested in it and whether it can be reused.
+/// Nested sources are flattened first, so a source and the sources nested in
+/// it take up consecutive indexes.
+fn collect_previous_sources(
+    node_kinds: &NodeKinds,
+    node: Node,
+    changed: &[Range],
+    sources: &mut Vec<((usize, usize), usize, bool)>,
+) {
+    let first = sources.len();
 
-            builder.take_expression(FlatExpression::Member(binary))?;
-        }
-        kind if kind == node_kinds.multiplicative => {
-            let mut binary_builder = FlatBinaryBuilder::new(builder);
+    for child in node.named_children(&mut node.walk()) {
+        collect_previous_sources(node_kinds, child, changed, sources);
+    }
 
-            for child in node.named_children(&mut node.walk()) {
-                flatten_node(&mut binary_builder, node_kinds, child, code)?;
-            }
+    if node.kind_id() == node_kinds.source || node.kind_id() == node_kinds.source_file {
+        let reusable = node.kind_id() == node_kinds.source
+            && !node.has_changes()
+            && !changed.iter().any(|range| {
+                range.start_byte < node.end_byte() && node.start_byte() < range.end_byte
+            });
 
-            let binary = binary_builder.binary()?;
+        sources.push(((node.start_byte(), node.end_byte()), first, reusable));
+    }
+}
 
-            builder.take_expression(FlatExpression::Multiplicative(binary))?;
-        }
-        kind if kind == node_kinds.additive => {
-            let mut binary_builder = FlatBinaryBuilder::new(builder);
+/// Whether every name used in the previous sources `first..=last` is bound in
+/// the source using it or a source around it, up to `last`. Checks of such a
+/// source do not depend on anything outside it.
+fn is_closed(previous: &FlatRoot, first: usize, last: usize) -> bool {
+    let Some(sources) = previous.sources.get(first..=last) else {
+        return false;
+    };
+
+    let mut parents = HashMap::new();
+    let mut bound = Vec::new();
+
+    for (index, source) in (first..).zip(sources) {
+        let mut names = HashSet::new();
+
+        for expression in &source.expressions {
+            if let FlatExpression::Assign(FlatBinary {
+                one: Some(FlatIndex::Identifier(target)),
+                ..
+            }) = expression
+                && let Some(target) = source.identifier(*target)
+            {
+                names.insert(target.name());
+            }
 
-            for child in node.named_children(&mut node.walk()) {
-                flatten_node(&mut binary_builder, node_kinds, child, code)?;
+            for operand in expression.operands() {
+                if let FlatIndex::Source(nested) = operand
+                    && *nested < index
+                {
+                    parents.insert(*nested, index);
+                }
             }
+        }
 
-            let binary = binary_builder.binary()?;
+        bound.push(names);
+    }
 
-            builder.take_expression(FlatExpression::Additive(binary))?;
-        }
-        kind if kind == node_kinds.comparison => {
-            let mut binary_builder = FlatBinaryBuilder::new(builder);
+    (first..).zip(sources).all(|(index, source)| {
+        source.identifiers.iter().all(|identifier| {
+            let mut scope = Some(index);
 
-            for child in node.named_children(&mut node.walk()) {
-                flatten_node(&mut binary_builder, node_kinds, child, code)?;
+            while let Some(current) = scope {
+                if bound[current - first].contains(identifier.name()) {
+                    return true;
+                }
+
+                scope = parents.get(&current).copied();
             }
 
-            let binary = binary_builder.binary()?;
+            false
+        })
+    })
+}
 
-            builder.take_expression(FlatExpression::Comparison(binary))?;
-        }
-        kind if kind == node_kinds.logical => {
-            let mut binary_builder = FlatBinaryBuilder::new(builder);
+fn mismatch() -> Error {
+    Error::FlattenError("Error: Previous root does not match the previous tree.".to_string())
+}
 
-            for child in node.named_children(&mut node.walk()) {
-                flatten_node(&mut binary_builder, node_kinds, child, code)?;
-            }
+fn flatten_with(
+    root_builder: &mut FlatRootBuilder,
+    node_kinds: &NodeKinds,
+    tree: &Tree,
+    code: &str,
+) -> Result<(), Error> {
+    let root_node = tree.root_node();
 
-            let binary = binary_builder.binary()?;
+    // Empty or whitespace-only input is a valid program without any sources.
+    if root_node.named_child_count() == 0 {
+        return Ok(());
+    }
 
-            builder.take_expression(FlatExpression::Logical(binary))?;
-        }
-        kind if kind == node_kinds.call => {
-            let mut binary_builder = FlatBinaryBuilder::new(builder);
+    flatten_node(root_builder, node_kinds, root_node, code)?;
 
-            for child in node.named_children(&mut node.walk()) {
-                flatten_node(&mut binary_builder, node_kinds, child, code)?;
-            }
+    // Copied sources that only use their own names passed these checks when
+    // the previous root was flattened.
+    let none = HashSet::new();
+    let closed = root_builder
+        .reuse
+        .as_ref()
+        .map_or(&none, |reuse| &reuse.closed);
 
-            let binary = binary_builder.binary()?;
+    let int_width = root_builder.int_width;
 
-            builder.take_expression(FlatExpression::Call(binary))?;
-        }
-        kind if kind == node_kinds.assign => {
-            let mut binary_builder = FlatBinaryBuilder::new(builder);
+    if let Some(error) = division_by_zero(node_kinds, root_node, code, int_width, closed) {
+        return Err(error);
+    }
 
-            for child in node.named_children(&mut node.walk()) {
-                flatten_node(&mut binary_builder, node_kinds, child, code)?;
-            }
+    if let Some(error) = type_error(node_kinds, root_node, code, closed) {
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+fn flatten_node<Builder: FlatBuilder>(
+    builder: &mut Builder,
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+) -> Result<(), Error> {
+    // Extras (comments, once the grammar has them) carry no value, but skipped
+    // tokens are extras too and must still surface as errors.
+    if node.is_extra() && !node.is_error() {
+        return Ok(());
+    }
 
-            let binary = binary_builder.binary()?;
+    if let Some(error) = node_error(node_kinds, node, code) {
+        return Err(error);
+    }
+
+    match node.kind_id() {
+        kind if kind == node_kinds.source_file || kind == node_kinds.source => {
+            let reused = kind == node_kinds.source && builder.take_reused_source(node)?;
+
+            if !reused {
+                flatten_source(builder, node_kinds, node, code)?;
+            }
+        }
+        kind if kind == node_kinds.member => {
+            flatten_binary(builder, node_kinds, node, code, FlatExpression::Member)?;
+        }
+        kind if kind == node_kinds.multiplicative => {
+            flatten_binary(
+                builder,
+                node_kinds,
+                node,
+                code,
+                FlatExpression::Multiplicative,
+            )?;
+        }
+        kind if kind == node_kinds.additive => {
+            flatten_binary(builder, node_kinds, node, code, FlatExpression::Additive)?;
+        }
+        kind if kind == node_kinds.comparison => {
+            let (expression, _) = flatten_comparison(builder, node_kinds, node, code)?;
 
-            builder.take_expression(FlatExpression::Assign(binary))?;
+            builder.take_expression(expression)?;
+        }
+        kind if kind == node_kinds.logical => {
+            flatten_binary(builder, node_kinds, node, code, FlatExpression::Logical)?;
+        }
+        kind if kind == node_kinds.call => {
+            flatten_binary(builder, node_kinds, node, code, FlatExpression::Call)?;
+        }
+        kind if kind == node_kinds.assign => {
+            flatten_binary(builder, node_kinds, node, code, FlatExpression::Assign)?;
         }
         kind if kind == node_kinds.parenthesize => {
             for child in node.named_children(&mut node.walk()) {
                 flatten_node(builder, node_kinds, child, code)?;
             }
         }
+        _ => flatten_leaf(builder, node_kinds, node, code)?,
+    }
+
+    Ok(())
+}
+
+/// Definitions without an expression and missing nodes, which the parser
+/// recovers from without an error node.
+#[inline(never)]
+fn node_error(node_kinds: &NodeKinds, node: Node, code: &str) -> Option<Error> {
+    if let Some(error) = definition_without_expression(node_kinds, node, code) {
+        return Some(error);
+    }
+
+    if node.is_missing() {
+        return Some(Error::MissingSyntax {
+            kind: node.kind().to_string(),
+            span: (node.start_byte(), node.end_byte()),
+        });
+    }
+
+    None
+}
+
+/// Literals, identifiers and operators, which have no children to flatten.
+#[inline(never)]
+fn flatten_leaf<Builder: FlatBuilder>(
+    builder: &mut Builder,
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+) -> Result<(), Error> {
+    let node_text = node.utf8_text(code.as_bytes()).map_err(|error| {
+        Error::FlattenError(format!("Error: Failed to extract UTF-8 text: {}.", error))
+    })?;
+
+    match node.kind_id() {
         kind if kind == node_kinds.binary
             || kind == node_kinds.octal
             || kind == node_kinds.decimal
             || kind == node_kinds.hex =>
         {
+            parse_literal(node_kinds, node, node_text, builder.int_width())?;
             builder.take_number(FlatNumber(node_text.to_string()))?;
         }
         kind if kind == node_kinds.single_quoted || kind == node_kinds.double_quoted => {
+            decode_string_at(node_text, node.start_byte())?;
             builder.take_string(FlatString(node_text.to_string()))?;
         }
+        kind if kind == node_kinds.identifier && is_number_literal(node_text) => {
+            parse_literal(node_kinds, node, node_text, builder.int_width())?;
+            builder.take_number(FlatNumber(node_text.to_string()))?;
+        }
+        kind if kind == node_kinds.identifier && is_malformed_number(node_text) => {
+            return Err(Error::InvalidNumber {
+                text: node_text.to_string(),
+                span: (node.start_byte(), node.end_byte()),
+            });
+        }
         kind if kind == node_kinds.identifier => {
             builder.take_identifier(FlatIdentifier(node_text.to_string()))?;
         }
@@ -182,16 +373,196 @@ fn flatten_node<Builder: FlatBuilder>(
             builder.operator(FlatOperator::Variable)?;
         }
         _ => {
-            return Err(Error::FlattenError(format!(
-                "Error: Cannot process node of unknown type {}.",
-                node.kind()
-            )));
+            return Err(Error::UnsupportedNode {
+                kind: node.kind().to_string(),
+                span: (node.start_byte(), node.end_byte()),
+            });
         }
     }
 
     Ok(())
 }
 
+// The helpers below hold the builders, so that the frame of `flatten_node`,
+// which every level of the tree adds to the stack, stays small.
+
+#[inline(never)]
+fn flatten_source<Builder: FlatBuilder>(
+    builder: &mut Builder,
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+) -> Result<(), Error> {
+    let mut source_builder = FlatSourceBuilder::new(builder);
+
+    for child in node.named_children(&mut node.walk()) {
+        flatten_node(&mut source_builder, node_kinds, child, code)?;
+    }
+
+    let source = source_builder.source()?;
+
+    builder.take_source(source)
+}
+
+#[inline(never)]
+fn flatten_binary<Builder: FlatBuilder>(
+    builder: &mut Builder,
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+    expression: fn(FlatBinary) -> FlatExpression,
+) -> Result<(), Error> {
+    let mut binary_builder = FlatBinaryBuilder::new(builder);
+
+    for child in node.named_children(&mut node.walk()) {
+        flatten_node(&mut binary_builder, node_kinds, child, code)?;
+    }
+
+    if is_mixed_definition_chain(node_kinds, node) {
+        return Err(Error::FlattenError(
+            "Error: Definition chain mixes constant `:` and variable `=` bindings.".to_string(),
+        ));
+    }
+
+    let binary = binary_builder.binary()?;
+
+    builder.take_expression(expression(binary))
+}
+
+/// `a < b < c` means `a < b && b < c`, as in mathematics, rather than
+/// comparing the result of `a < b` with `c`. The middle operand is flattened
+/// once and both comparisons refer to it; parenthesize a comparison to use
+/// its result as an operand. Returns the expression together with its right
+/// operand, which the next link of a chain compares against.
+fn flatten_comparison<Builder: FlatBuilder>(
+    builder: &mut Builder,
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+) -> Result<(FlatExpression, FlatIndex), Error> {
+    let (previous, middle) = match node.named_child(0) {
+        Some(left) if left.kind_id() == node_kinds.comparison => {
+            let (expression, middle) = flatten_comparison(builder, node_kinds, left, code)?;
+
+            (Some(builder.send_expression(expression)?), Some(middle))
+        }
+        _ => (None, None),
+    };
+
+    let mut binary_builder = FlatBinaryBuilder::new(builder);
+
+    for (position, child) in node.named_children(&mut node.walk()).enumerate() {
+        match &middle {
+            Some(middle) if position == 0 => binary_builder.index(middle.clone())?,
+            _ => flatten_node(&mut binary_builder, node_kinds, child, code)?,
+        }
+    }
+
+    let binary = binary_builder.binary()?;
+    let two = binary.two.clone();
+
+    let Some(previous) = previous else {
+        return Ok((FlatExpression::Comparison(binary), two));
+    };
+
+    let comparison = builder.send_expression(FlatExpression::Comparison(binary))?;
+
+    Ok((
+        FlatExpression::Logical(FlatBinary {
+            one: Some(previous),
+            two: comparison,
+            operator: FlatOperator::And,
+        }),
+        two,
+    ))
+}
+
+fn parse_literal(
+    node_kinds: &NodeKinds,
+    node: Node,
+    text: &str,
+    int_width: IntWidth,
+) -> Result<i64, Error> {
+    let value = if is_negated(node_kinds, node) {
+        int_width.parse_negated(text)
+    } else {
+        int_width.parse(text)
+    };
+
+    value.map_err(|error| error.with_span((node.start_byte(), node.end_byte())))
+}
+
+/// `a : b = 0d5` binds both names to the same value, so every link of a
+/// chain has to agree on whether the names are constant or variable.
+pub(crate) fn is_mixed_definition_chain(node_kinds: &NodeKinds, node: Node) -> bool {
+    if node.kind_id() != node_kinds.assign {
+        return false;
+    }
+
+    let Some(value) = node.named_child(2) else {
+        return false;
+    };
+
+    value.kind_id() == node_kinds.assign
+        && node.named_child(1).map(|operator| operator.kind_id())
+            != value.named_child(1).map(|operator| operator.kind_id())
+}
+
+/// Whether `node` is the operand of a unary minus, as in `-0d5`. The literal
+/// is then parsed together with its sign, so that it may reach the smallest
+/// value of its width.
+pub(crate) fn is_negated(node_kinds: &NodeKinds, node: Node) -> bool {
+    node.parent().is_some_and(|parent| {
+        parent.kind_id() == node_kinds.additive
+            && parent.named_child_count() == 2
+            && parent
+                .named_child(0)
+                .is_some_and(|operator| operator.kind_id() == node_kinds.subtract)
+            && parent.named_child(1) == Some(node)
+    })
+}
+
+/// Recognises `x :` with nothing to bind, which the parser recovers from
+/// either with a missing right operand or by wrapping the operator in an
+/// error next to the name.
+pub(crate) fn definition_without_expression(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+) -> Option<Error> {
+    let (target, end) = if node.kind_id() == node_kinds.assign {
+        let value = node.named_child(node.named_child_count().checked_sub(1)?)?;
+
+        if !value.is_missing() {
+            return None;
+        }
+
+        (node.named_child(0)?, node.end_byte())
+    } else if node.is_error() {
+        let operator = node.named_child(0)?;
+
+        if node.child_count() != 1
+            || (operator.kind_id() != node_kinds.constant
+                && operator.kind_id() != node_kinds.variable)
+        {
+            return None;
+        }
+
+        (node.prev_named_sibling()?, node.end_byte())
+    } else {
+        return None;
+    };
+
+    if target.kind_id() != node_kinds.identifier {
+        return None;
+    }
+
+    Some(Error::DefinitionWithoutExpression {
+        name: code[target.byte_range()].to_string(),
+        span: (target.start_byte(), end),
+    })
+}
+
 trait FlatBuilder {
     fn send_source(&mut self, source: FlatSource) -> Result<FlatIndex, Error>;
     fn take_source(&mut self, source: FlatSource) -> Result<(), Error> {
@@ -223,8 +594,18 @@ trait FlatBuilder {
         Ok(())
     }
 
+    /// Index of `node` copied from the previous root, when it is a source
+    /// that `flatten_incremental` can reuse.
+    fn send_reused_source(&mut self, node: Node) -> Result<Option<FlatIndex>, Error>;
+    fn take_reused_source(&mut self, node: Node) -> Result<bool, Error> {
+        Ok(self.send_reused_source(node)?.is_some())
+    }
+
     fn index(&mut self, index: FlatIndex) -> Result<(), Error>;
     fn operator(&mut self, operator: FlatOperator) -> Result<(), Error>;
+
+    /// Width number literals have to fit in.
+    fn int_width(&self) -> IntWidth;
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -234,19 +615,218 @@ pub struct FlatRoot {
     pub strings: Vec<FlatString>,
 }
 
-pub struct FlatRootBuilder {
+/// Lookups are bounds-checked, so indexes from a deserialized root can be
+/// followed without trusting them.
+impl FlatRoot {
+    pub fn source(&self, index: usize) -> Option<&FlatSource> {
+        self.sources.get(index)
+    }
+
+    pub fn number(&self, index: usize) -> Option<&FlatNumber> {
+        self.numbers.get(index)
+    }
+
+    pub fn string(&self, index: usize) -> Option<&FlatString> {
+        self.strings.get(index)
+    }
+
+    pub fn source_count(&self) -> usize {
+        self.sources.len()
+    }
+}
+
+impl<'a> IntoIterator for &'a FlatRoot {
+    type Item = &'a FlatSource;
+    type IntoIter = std::slice::Iter<'a, FlatSource>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sources.iter()
+    }
+}
+
+/// Builds a `FlatRoot`. `number_indexes` and `string_indexes` map the
+/// canonical form of each number and the decoded value of each string to
+/// its index, so deduplicating does not recompute them for every stored
+/// literal.
+pub struct FlatRootBuilder<'a> {
     sources: Vec<FlatSource>,
     numbers: Vec<FlatNumber>,
     strings: Vec<FlatString>,
+    number_indexes: HashMap<String, usize>,
+    string_indexes: HashMap<String, usize>,
+    int_width: IntWidth,
+    reuse: Option<Reuse<'a>>,
+}
+
+/// Sources of the previous root that `flatten_incremental` may copy, by their
+/// span in the new tree, with the index of the first source nested in them.
+/// `numbers` and `strings` map indexes of the previous root to indexes of the
+/// new one as copied sources meet them. `closed` holds the spans of copied
+/// sources that only use names bound inside them, which the checks after
+/// flattening can skip.
+struct Reuse<'a> {
+    previous: &'a FlatRoot,
+    reusable: HashMap<(usize, usize), (usize, usize)>,
+    numbers: HashMap<usize, usize>,
+    strings: HashMap<usize, usize>,
+    closed: HashSet<(usize, usize)>,
+    reused: usize,
+}
+
+impl<'a> Reuse<'a> {
+    fn new(previous: &'a FlatRoot, reusable: HashMap<(usize, usize), (usize, usize)>) -> Self {
+        Self {
+            previous,
+            reusable,
+            numbers: HashMap::new(),
+            strings: HashMap::new(),
+            closed: HashSet::new(),
+            reused: 0,
+        }
+    }
+
+    /// `index` of the previous root moved into the new one, where the
+    /// sources from `first` on start at `base`.
+    fn remap(&self, index: &FlatIndex, first: usize, base: usize) -> Result<FlatIndex, Error> {
+        match index {
+            FlatIndex::Number(number) => {
+                self.numbers.get(number).map(|new| FlatIndex::Number(*new))
+            }
+            FlatIndex::String(string) => {
+                self.strings.get(string).map(|new| FlatIndex::String(*new))
+            }
+            FlatIndex::Source(source) => source
+                .checked_sub(first)
+                .map(|offset| FlatIndex::Source(base + offset)),
+            FlatIndex::Expression(_) | FlatIndex::Identifier(_) => Some(index.clone()),
+        }
+        .ok_or_else(mismatch)
+    }
 }
 
-impl FlatRootBuilder {
-    fn new() -> Self {
+impl<'a> FlatRootBuilder<'a> {
+    fn new(int_width: IntWidth) -> Self {
         Self {
             sources: Vec::new(),
             numbers: Vec::new(),
             strings: Vec::new(),
+            number_indexes: HashMap::new(),
+            string_indexes: HashMap::new(),
+            int_width,
+            reuse: None,
+        }
+    }
+
+    /// Sends the numbers and strings of the previous sources `first..=last`
+    /// in the order flattening meets them, so that they are deduplicated as
+    /// if the sources were flattened again, and records their new indexes.
+    /// Operands come in source order when expressions are followed from the
+    /// ones no other expression uses, first operand first. Sources that no
+    /// expression uses, such as the inner one of `{ { z : 0d3 } }`, are
+    /// followed afterwards, in the order they were flattened.
+    fn send_reused_literals(
+        &mut self,
+        reuse: &mut Reuse,
+        first: usize,
+        last: usize,
+    ) -> Result<(), Error> {
+        let previous = reuse.previous;
+        let mut visited = HashSet::new();
+        let mut sources = HashSet::new();
+        let mut starts = std::iter::once(last).chain(first..last);
+        let mut stack = Vec::new();
+
+        loop {
+            let Some((source, index)) = stack.pop() else {
+                match starts.find(|start| !sources.contains(start)) {
+                    Some(start) => stack.push((start, FlatIndex::Source(start))),
+                    None => break,
+                }
+
+                continue;
+            };
+
+            match index {
+                FlatIndex::Number(number) if !reuse.numbers.contains_key(&number) => {
+                    let value = previous.number(number).ok_or_else(mismatch)?;
+                    let FlatIndex::Number(new) = self.send_number(value.clone())? else {
+                        return Err(mismatch());
+                    };
+
+                    reuse.numbers.insert(number, new);
+                }
+                FlatIndex::String(string) if !reuse.strings.contains_key(&string) => {
+                    let value = previous.string(string).ok_or_else(mismatch)?;
+                    let FlatIndex::String(new) = self.send_string(value.clone())? else {
+                        return Err(mismatch());
+                    };
+
+                    reuse.strings.insert(string, new);
+                }
+                FlatIndex::Source(nested) if sources.insert(nested) => {
+                    if !(first..=last).contains(&nested) {
+                        return Err(mismatch());
+                    }
+
+                    let expressions = &previous.source(nested).ok_or_else(mismatch)?.expressions;
+                    let used: HashSet<usize> = expressions
+                        .iter()
+                        .flat_map(FlatExpression::operands)
+                        .filter_map(|operand| match operand {
+                            FlatIndex::Expression(expression) => Some(*expression),
+                            _ => None,
+                        })
+                        .collect();
+
+                    for expression in (0..expressions.len()).rev() {
+                        if !used.contains(&expression) {
+                            stack.push((nested, FlatIndex::Expression(expression)));
+                        }
+                    }
+                }
+                FlatIndex::Expression(expression) if visited.insert((source, expression)) => {
+                    let expression = previous
+                        .source(source)
+                        .and_then(|source| source.expression(expression))
+                        .ok_or_else(mismatch)?;
+
+                    for operand in expression.operands().rev() {
+                        stack.push((source, operand.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies the previous sources `first..=last` to the end of this root.
+    fn copy_sources(&mut self, reuse: &mut Reuse, first: usize, last: usize) -> Result<(), Error> {
+        let sources = reuse
+            .previous
+            .sources
+            .get(first..=last)
+            .ok_or_else(mismatch)?;
+
+        self.send_reused_literals(reuse, first, last)?;
+
+        let base = self.sources.len();
+
+        for source in sources {
+            let expressions = source
+                .expressions
+                .iter()
+                .map(|expression| expression.remap(|index| reuse.remap(index, first, base)))
+                .collect::<Result<_, Error>>()?;
+
+            self.sources.push(FlatSource {
+                expressions,
+                identifiers: source.identifiers.clone(),
+            });
         }
+
+        Ok(())
     }
 
     fn root(self) -> Result<FlatRoot, Error> {
@@ -258,7 +838,7 @@ impl FlatRootBuilder {
     }
 }
 
-impl FlatBuilder for FlatRootBuilder {
+impl<'a> FlatBuilder for FlatRootBuilder<'a> {
     fn send_source(&mut self, source: FlatSource) -> Result<FlatIndex, Error> {
         let index = FlatIndex::Source(self.sources.len());
         self.sources.push(source);
@@ -272,23 +852,29 @@ impl FlatBuilder for FlatRootBuilder {
     }
 
     fn send_number(&mut self, number: FlatNumber) -> Result<FlatIndex, Error> {
-        if let Some(position) = self.numbers.iter().position(|current| *current == number) {
-            return Ok(FlatIndex::Number(position));
+        let position = *self
+            .number_indexes
+            .entry(number.canonical())
+            .or_insert(self.numbers.len());
+
+        if position == self.numbers.len() {
+            self.numbers.push(number);
         }
 
-        let index = FlatIndex::Number(self.numbers.len());
-        self.numbers.push(number);
-        Ok(index)
+        Ok(FlatIndex::Number(position))
     }
 
     fn send_string(&mut self, string: FlatString) -> Result<FlatIndex, Error> {
-        if let Some(position) = self.strings.iter().position(|current| *current == string) {
-            return Ok(FlatIndex::String(position));
+        let position = *self
+            .string_indexes
+            .entry(string.value())
+            .or_insert(self.strings.len());
+
+        if position == self.strings.len() {
+            self.strings.push(string);
         }
 
-        let index = FlatIndex::String(self.strings.len());
-        self.strings.push(string);
-        Ok(index)
+        Ok(FlatIndex::String(position))
     }
 
     fn send_identifier(&mut self, _: FlatIdentifier) -> Result<FlatIndex, Error> {
@@ -297,6 +883,35 @@ impl FlatBuilder for FlatRootBuilder {
         ))
     }
 
+    fn send_reused_source(&mut self, node: Node) -> Result<Option<FlatIndex>, Error> {
+        let span = (node.start_byte(), node.end_byte());
+
+        let Some(mut reuse) = self.reuse.take() else {
+            return Ok(None);
+        };
+
+        let Some(&(first, last)) = reuse.reusable.get(&span) else {
+            self.reuse = Some(reuse);
+
+            return Ok(None);
+        };
+
+        let result = self.copy_sources(&mut reuse, first, last);
+
+        if result.is_ok() {
+            reuse.reused += last + 1 - first;
+
+            if is_closed(reuse.previous, first, last) {
+                reuse.closed.insert(span);
+            }
+        }
+
+        self.reuse = Some(reuse);
+        result?;
+
+        Ok(Some(FlatIndex::Source(self.sources.len() - 1)))
+    }
+
     fn index(&mut self, _: FlatIndex) -> Result<(), Error> {
         Err(Error::FlattenError(
             "Error: Invalid syntax - indexes cannot be placed at the root level; they must be inside expressions.".to_string(),
@@ -308,6 +923,9 @@ impl FlatBuilder for FlatRootBuilder {
             "Error: Invalid syntax - operators cannot be placed at the root level; they must be inside expressions.".to_string(),
         ))
     }
+    fn int_width(&self) -> IntWidth {
+        self.int_width
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -316,6 +934,33 @@ pub struct FlatSource {
     pub identifiers: Vec<FlatIdentifier>,
 }
 
+impl FlatSource {
+    pub fn expression(&self, index: usize) -> Option<&FlatExpression> {
+        self.expressions.get(index)
+    }
+
+    pub fn identifier(&self, index: usize) -> Option<&FlatIdentifier> {
+        self.identifiers.get(index)
+    }
+
+    pub fn expression_count(&self) -> usize {
+        self.expressions.len()
+    }
+
+    pub fn identifier_count(&self) -> usize {
+        self.identifiers.len()
+    }
+}
+
+impl<'a> IntoIterator for &'a FlatSource {
+    type Item = &'a FlatExpression;
+    type IntoIter = std::slice::Iter<'a, FlatExpression>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.expressions.iter()
+    }
+}
+
 pub struct FlatSourceBuilder<'a> {
     parent: &'a mut dyn FlatBuilder,
     expressions: Vec<FlatExpression>,
@@ -372,6 +1017,10 @@ impl<'a> FlatBuilder for FlatSourceBuilder<'a> {
         Ok(index)
     }
 
+    fn send_reused_source(&mut self, node: Node) -> Result<Option<FlatIndex>, Error> {
+        self.parent.send_reused_source(node)
+    }
+
     fn index(&mut self, _: FlatIndex) -> Result<(), Error> {
         Err(Error::FlattenError(
             "Error: Invalid syntax - indexes cannot be placed directly in a source block; they must be inside binary expressions.".to_string(),
@@ -383,6 +1032,9 @@ impl<'a> FlatBuilder for FlatSourceBuilder<'a> {
             "Error: Invalid syntax - operators cannot be placed directly in a source block; they must be inside binary expressions.".to_string(),
         ))
     }
+    fn int_width(&self) -> IntWidth {
+        self.parent.int_width()
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -470,6 +1122,17 @@ impl<'a> FlatBuilder for FlatBinaryBuilder<'a> {
         self.index(index)
     }
 
+    fn send_reused_source(&mut self, node: Node) -> Result<Option<FlatIndex>, Error> {
+        self.parent.send_reused_source(node)
+    }
+
+    fn take_reused_source(&mut self, node: Node) -> Result<bool, Error> {
+        match self.send_reused_source(node)? {
+            Some(index) => self.index(index).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
     fn index(&mut self, index: FlatIndex) -> Result<(), Error> {
         if self.one.is_none() && self.operator.is_none() {
             self.one = Some(index);
@@ -495,6 +1158,9 @@ impl<'a> FlatBuilder for FlatBinaryBuilder<'a> {
 
         Ok(())
     }
+    fn int_width(&self) -> IntWidth {
+        self.parent.int_width()
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -508,15 +1174,81 @@ pub enum FlatExpression {
     Assign(FlatBinary),
 }
 
+impl FlatExpression {
+    fn binary(&self) -> &FlatBinary {
+        let (FlatExpression::Member(binary)
+        | FlatExpression::Call(binary)
+        | FlatExpression::Multiplicative(binary)
+        | FlatExpression::Additive(binary)
+        | FlatExpression::Comparison(binary)
+        | FlatExpression::Logical(binary)
+        | FlatExpression::Assign(binary)) = self;
+
+        binary
+    }
+
+    /// Operands in source order.
+    pub(crate) fn operands(&self) -> impl DoubleEndedIterator<Item = &FlatIndex> {
+        let binary = self.binary();
+
+        binary.one.iter().chain([&binary.two])
+    }
+
+    /// Same expression with each operand replaced by `f` of it.
+    fn remap(
+        &self,
+        mut f: impl FnMut(&FlatIndex) -> Result<FlatIndex, Error>,
+    ) -> Result<FlatExpression, Error> {
+        let binary = self.binary();
+        let binary = FlatBinary {
+            one: binary.one.as_ref().map(&mut f).transpose()?,
+            two: f(&binary.two)?,
+            operator: binary.operator.clone(),
+        };
+
+        Ok(match self {
+            FlatExpression::Member(_) => FlatExpression::Member(binary),
+            FlatExpression::Call(_) => FlatExpression::Call(binary),
+            FlatExpression::Multiplicative(_) => FlatExpression::Multiplicative(binary),
+            FlatExpression::Additive(_) => FlatExpression::Additive(binary),
+            FlatExpression::Comparison(_) => FlatExpression::Comparison(binary),
+            FlatExpression::Logical(_) => FlatExpression::Logical(binary),
+            FlatExpression::Assign(_) => FlatExpression::Assign(binary),
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct FlatNumber(String);
 
+impl FlatNumber {
+    /// Literal with the radix prefix and digits lowercased and digit
+    /// separators removed, so `0XFF`, `0xff` and `0xf_f` compare equal.
+    pub fn canonical(&self) -> String {
+        self.0.to_ascii_lowercase().replace('_', "")
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct FlatString(String);
 
+impl FlatString {
+    /// Decoded value without quotes, so `"\n"` and `'\u{a}'` compare equal.
+    /// Literals are checked when flattened, so decoding does not fail here.
+    pub fn value(&self) -> String {
+        decode_string(&self.0).unwrap_or_else(|_| self.0.clone())
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct FlatIdentifier(String);
 
+impl FlatIdentifier {
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum FlatIndex {
     Source(usize),
@@ -1 +1,606 @@
+use tree_sitter::{InputEdit, Point};
 
+use crate::{
+    Error, ErrorCode, FlatBinary, FlatExpression, FlatIndex, FlatOperator, FlatRoot,
+    IncrementalFlatten, IntWidth, Mage, Stage, Type, flatten_incremental, flatten_tree,
+};
+
+fn flatten(code: &str) -> Result<FlatRoot, Error> {
+    let mut mage = Mage::new()?;
+
+    match mage.process(&Stage::Flatten, code)? {
+        Type::Flat(root) => Ok(root),
+        other => panic!("Expected flat root, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_empty_input() {
+    let root = flatten("").unwrap();
+
+    assert!(root.sources.is_empty());
+    assert!(root.numbers.is_empty());
+    assert!(root.strings.is_empty());
+}
+
+#[test]
+fn test_whitespace_input() {
+    assert_eq!(flatten("   \n"), flatten(""));
+}
+
+#[test]
+fn test_number_casing_deduplicates() {
+    let root = flatten("0XFF + 0xff").unwrap();
+
+    assert_eq!(root.numbers.len(), 1);
+    assert_eq!(
+        root.sources[0].expressions,
+        vec![FlatExpression::Additive(FlatBinary {
+            one: Some(FlatIndex::Number(0)),
+            two: FlatIndex::Number(0),
+            operator: FlatOperator::Add,
+        })]
+    );
+}
+
+#[test]
+fn test_trivia_is_ignored() {
+    assert_eq!(
+        flatten("a : 0d1\n\n\tb : a   *   0d2\n").unwrap(),
+        flatten("a : 0d1 b : a * 0d2").unwrap()
+    );
+}
+
+#[test]
+fn test_unsupported_node_span() {
+    assert_eq!(
+        flatten("a : 0d1 )"),
+        Err(Error::UnsupportedNode {
+            kind: "ERROR".to_string(),
+            span: (8, 9),
+        })
+    );
+}
+
+#[test]
+fn test_flatten_borrowed_tree() {
+    let code = "a : 0d1 + b";
+
+    let mut mage = Mage::new().unwrap();
+    let node_kinds = mage.node_kinds();
+    let tree = mage.parse_text(code).unwrap();
+
+    let first = flatten_tree(node_kinds, &tree, code, IntWidth::I64).unwrap();
+    let second = flatten_tree(node_kinds, &tree, code, IntWidth::I64).unwrap();
+
+    assert_eq!(first, second);
+    assert!(!tree.root_node().has_error());
+    assert_eq!(tree.root_node().named_child_count(), 1);
+}
+
+#[test]
+fn test_flatten_snapshot() {
+    let root = flatten("a : 0d2\nb = (a + 0x10) * 0d2\n{ c : 'x' => print }\nb . c == a || 0x10\n")
+        .unwrap();
+
+    assert_eq!(
+        serde_json::to_string(&root).unwrap(),
+        r#"{"sources":[{"expressions":[{"Call":{"one":{"String":0},"two":{"Identifier":1},"operator":"Pipe"}},{"Assign":{"one":{"Identifier":0},"two":{"Expression":0},"operator":"Constant"}}],"identifiers":["c","print"]},{"expressions":[{"Assign":{"one":{"Identifier":0},"two":{"Number":0},"operator":"Constant"}},{"Additive":{"one":{"Identifier":0},"two":{"Number":1},"operator":"Add"}},{"Multiplicative":{"one":{"Expression":1},"two":{"Number":0},"operator":"Multiply"}},{"Assign":{"one":{"Identifier":1},"two":{"Expression":2},"operator":"Variable"}},{"Member":{"one":{"Identifier":1},"two":{"Identifier":2},"operator":"Extract"}},{"Comparison":{"one":{"Expression":4},"two":{"Identifier":0},"operator":"Equal"}},{"Logical":{"one":{"Expression":5},"two":{"Number":1},"operator":"Or"}}],"identifiers":["a","b","c"]}],"numbers":["0d2","0x10"],"strings":["'x'"]}"#
+    );
+}
+
+#[test]
+fn test_resolve_indexes() {
+    let root = flatten("a : 0d2 * 0d3\ns : 'x'\nb : { a }").unwrap();
+    let source = root.source(1).unwrap();
+
+    let FlatExpression::Multiplicative(FlatBinary {
+        one: Some(FlatIndex::Number(one)),
+        two: FlatIndex::Number(two),
+        ..
+    }) = source.expression(0).unwrap()
+    else {
+        panic!("Expected product, got {:?}", source.expression(0));
+    };
+
+    assert_eq!(root.number(*one).unwrap().canonical(), "0d2");
+    assert_eq!(root.number(*two).unwrap().canonical(), "0d3");
+    assert_eq!(root.string(0).unwrap().value(), "x");
+    assert_eq!(source.identifier(1).unwrap().name(), "s");
+
+    assert!(root.source(2).is_none());
+    assert!(root.number(2).is_none());
+    assert!(root.string(1).is_none());
+    assert!(source.expression(10).is_none());
+    assert!(source.identifier(3).is_none());
+}
+
+#[test]
+fn test_counts_and_iteration() {
+    let root = flatten("a : 0d2\nb = (a + 0x10) * 0d2\n{ c : 'x' => print }\nb . c == a || 0x10\n")
+        .unwrap();
+
+    assert_eq!(root.source_count(), 2);
+    assert_eq!(root.source(0).unwrap().expression_count(), 2);
+    assert_eq!(root.source(1).unwrap().expression_count(), 7);
+    assert_eq!(root.source(1).unwrap().identifier_count(), 3);
+
+    let expressions: usize = (&root)
+        .into_iter()
+        .map(|source| source.into_iter().count())
+        .sum();
+
+    assert_eq!(expressions, 9);
+
+    let mut members = 0;
+
+    for source in &root {
+        for expression in source {
+            if let FlatExpression::Member(_) = expression {
+                members += 1;
+            }
+        }
+    }
+
+    assert_eq!(members, 1);
+}
+
+#[test]
+fn test_grouped_number_literal() {
+    let root = flatten("0d1_000 + 0d1000").unwrap();
+
+    assert_eq!(root.numbers.len(), 1);
+    assert!(root.sources[0].identifiers.is_empty());
+}
+
+#[test]
+fn test_number_literal_errors() {
+    let error = flatten("x : 0b12").unwrap_err();
+
+    assert_eq!(
+        error,
+        Error::InvalidNumber {
+            text: "0b12".to_string(),
+            span: (4, 8),
+        }
+    );
+    assert_eq!(error.code(), Some(ErrorCode::InvalidNumber));
+
+    let error = flatten("x : 0x1_0000_0000_0000_0000").unwrap_err();
+
+    assert_eq!(error.span(), Some((4, 27)));
+    assert_eq!(error.code(), Some(ErrorCode::NumberOutOfRange));
+}
+
+#[test]
+fn test_definition_without_expression() {
+    assert_eq!(
+        flatten("x :"),
+        Err(Error::DefinitionWithoutExpression {
+            name: "x".to_string(),
+            span: (0, 3),
+        })
+    );
+}
+
+#[test]
+fn test_definition_chain() {
+    let root = flatten("a : b : 0d5").unwrap();
+
+    assert_eq!(
+        root.sources[0].expressions,
+        vec![
+            FlatExpression::Assign(FlatBinary {
+                one: Some(FlatIndex::Identifier(1)),
+                two: FlatIndex::Number(0),
+                operator: FlatOperator::Constant,
+            }),
+            FlatExpression::Assign(FlatBinary {
+                one: Some(FlatIndex::Identifier(0)),
+                two: FlatIndex::Expression(0),
+                operator: FlatOperator::Constant,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_division_by_zero() {
+    assert_eq!(
+        flatten("a / 0d0"),
+        Err(Error::DivisionByZero { span: (0, 7) })
+    );
+    assert_eq!(
+        flatten("x : 0d0\n{ a / x }"),
+        Err(Error::DivisionByZero { span: (10, 15) })
+    );
+    assert!(flatten("x : 0d0\n{ x : 0d1\na / x }").is_ok());
+}
+
+#[test]
+fn test_int_width() {
+    let mut mage = Mage::new().unwrap();
+    mage.int_width = IntWidth::I32;
+
+    assert!(matches!(
+        mage.flatten("a : 0d3000000000"),
+        Err(Error::NumberOutOfRange { bits: 32, .. })
+    ));
+    assert_eq!(
+        mage.flatten("a / (0x4000_0000 * 0d4)"),
+        Err(Error::DivisionByZero { span: (0, 23) })
+    );
+    assert!(flatten("a / (0x4000_0000 * 0d4)").is_ok());
+}
+
+#[test]
+fn test_negated_literal() {
+    let root = flatten("x : -0d5").unwrap();
+
+    assert_eq!(
+        root.sources[0].expressions,
+        vec![
+            FlatExpression::Additive(FlatBinary {
+                one: None,
+                two: FlatIndex::Number(0),
+                operator: FlatOperator::Subtract,
+            }),
+            FlatExpression::Assign(FlatBinary {
+                one: Some(FlatIndex::Identifier(0)),
+                two: FlatIndex::Expression(0),
+                operator: FlatOperator::Constant,
+            }),
+        ]
+    );
+
+    assert!(flatten("x : -0d9_223_372_036_854_775_808").is_ok());
+    assert_eq!(
+        flatten("x : -0d5\n0d1 / (x + 0d5)"),
+        Err(Error::DivisionByZero { span: (9, 24) })
+    );
+}
+
+#[test]
+fn test_type_errors() {
+    assert_eq!(
+        flatten("a : \"a\" * 0d2"),
+        Err(Error::TypeError {
+            message: "Error: Cannot apply `*` to a string.".to_string(),
+            span: (4, 13),
+        })
+    );
+    assert_eq!(
+        flatten("s : 'a'\nn : s + 0d1"),
+        Err(Error::TypeError {
+            message: "Error: Cannot apply `+` to a string and a number.".to_string(),
+            span: (12, 19),
+        })
+    );
+    assert_eq!(
+        flatten("0d1 => 'print'"),
+        Err(Error::TypeError {
+            message: "Error: Cannot call a string.".to_string(),
+            span: (0, 14),
+        })
+    );
+
+    assert!(flatten("a : \"a\" + \"b\"").is_ok());
+    assert!(flatten("'a' < 'b' < 'c'").is_ok());
+    assert!(flatten("x = 'a'\nx * 0d2").is_ok());
+    assert!(flatten("f : { x : 0d1 => f }").is_ok());
+}
+
+/// `name0 : first`, then each name bound to the previous one added to
+/// itself, so that naive inference visits the first definition 2^length
+/// times.
+fn doubling_chain(name: &str, first: &str, length: usize) -> String {
+    let mut code = format!("{}0 : {}\n", name, first);
+
+    for index in 1..=length {
+        code.push_str(&format!(
+            "{}{} : {}{} + {}{}\n",
+            name,
+            index,
+            name,
+            index - 1,
+            name,
+            index - 1
+        ));
+    }
+
+    code
+}
+
+#[test]
+fn test_type_check_long_chain() {
+    let code = format!("{}n : s40 * 0d2", doubling_chain("s", "'a'", 40));
+
+    assert!(matches!(flatten(&code), Err(Error::TypeError { .. })));
+}
+
+#[test]
+fn test_division_by_long_chain() {
+    let code = format!("{}z : 0d1 / (a40 - a40)", doubling_chain("a", "0d1", 40));
+
+    assert!(matches!(flatten(&code), Err(Error::DivisionByZero { .. })));
+}
+
+#[test]
+fn test_comparison_chain() {
+    let root = flatten("a < b <= c").unwrap();
+
+    assert_eq!(
+        root.sources[0].expressions,
+        vec![
+            FlatExpression::Comparison(FlatBinary {
+                one: Some(FlatIndex::Identifier(0)),
+                two: FlatIndex::Identifier(1),
+                operator: FlatOperator::LessThan,
+            }),
+            FlatExpression::Comparison(FlatBinary {
+                one: Some(FlatIndex::Identifier(1)),
+                two: FlatIndex::Identifier(2),
+                operator: FlatOperator::LessEqual,
+            }),
+            FlatExpression::Logical(FlatBinary {
+                one: Some(FlatIndex::Expression(0)),
+                two: FlatIndex::Expression(1),
+                operator: FlatOperator::And,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_comparison_chain_evaluates_middle_once() {
+    let root = flatten("a < b + 0d1 < c").unwrap();
+    let expressions = &root.sources[0].expressions;
+
+    assert_eq!(
+        expressions
+            .iter()
+            .filter(|expression| matches!(expression, FlatExpression::Additive(_)))
+            .count(),
+        1
+    );
+    assert!(matches!(
+        &expressions[2],
+        FlatExpression::Comparison(FlatBinary {
+            one: Some(FlatIndex::Expression(0)),
+            ..
+        })
+    ));
+
+    let root = flatten("(a < b) < c").unwrap();
+
+    assert!(
+        root.sources[0]
+            .expressions
+            .iter()
+            .all(|expression| !matches!(expression, FlatExpression::Logical(_)))
+    );
+}
+
+#[test]
+fn test_parentheses_group_first() {
+    let grouped = flatten("a * (b + c)").unwrap();
+
+    assert_eq!(
+        grouped.sources[0].expressions,
+        vec![
+            FlatExpression::Additive(FlatBinary {
+                one: Some(FlatIndex::Identifier(1)),
+                two: FlatIndex::Identifier(2),
+                operator: FlatOperator::Add,
+            }),
+            FlatExpression::Multiplicative(FlatBinary {
+                one: Some(FlatIndex::Identifier(0)),
+                two: FlatIndex::Expression(0),
+                operator: FlatOperator::Multiply,
+            }),
+        ]
+    );
+
+    let ungrouped = flatten("a * b + c").unwrap();
+
+    assert_eq!(
+        ungrouped.sources[0].expressions,
+        vec![
+            FlatExpression::Multiplicative(FlatBinary {
+                one: Some(FlatIndex::Identifier(0)),
+                two: FlatIndex::Identifier(1),
+                operator: FlatOperator::Multiply,
+            }),
+            FlatExpression::Additive(FlatBinary {
+                one: Some(FlatIndex::Expression(0)),
+                two: FlatIndex::Identifier(2),
+                operator: FlatOperator::Add,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_nested_parentheses() {
+    let root = flatten("((a - b)) * (c - (d / e))").unwrap();
+
+    assert_eq!(
+        root.sources[0].expressions,
+        vec![
+            FlatExpression::Additive(FlatBinary {
+                one: Some(FlatIndex::Identifier(0)),
+                two: FlatIndex::Identifier(1),
+                operator: FlatOperator::Subtract,
+            }),
+            FlatExpression::Multiplicative(FlatBinary {
+                one: Some(FlatIndex::Identifier(3)),
+                two: FlatIndex::Identifier(4),
+                operator: FlatOperator::Divide,
+            }),
+            FlatExpression::Additive(FlatBinary {
+                one: Some(FlatIndex::Identifier(2)),
+                two: FlatIndex::Expression(1),
+                operator: FlatOperator::Subtract,
+            }),
+            FlatExpression::Multiplicative(FlatBinary {
+                one: Some(FlatIndex::Expression(0)),
+                two: FlatIndex::Expression(2),
+                operator: FlatOperator::Multiply,
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_strings_deduplicate_by_value() {
+    let root = flatten(r#"a : "\n" + '\u{a}' + "\x0A" + "n""#).unwrap();
+
+    assert_eq!(root.strings.len(), 2);
+    assert_eq!(root.strings[0].value(), "\n");
+    assert_eq!(root.strings[1].value(), "n");
+}
+
+fn point_at(code: &str, offset: usize) -> Point {
+    let line_start = code[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+
+    Point::new(code[..offset].matches('\n').count(), offset - line_start)
+}
+
+#[test]
+fn test_incremental_flatten_reuses_unchanged_sources() {
+    let definitions: Vec<String> = (0..100)
+        .map(|index| format!("a{} : {{ value : 0d{} }}", index, index))
+        .collect();
+    let code = definitions.join("\n");
+
+    let mut mage = Mage::new().unwrap();
+    let node_kinds = mage.node_kinds();
+
+    let mut old_tree = mage.parse_text(&code).unwrap();
+    let previous = flatten_tree(node_kinds, &old_tree, &code, IntWidth::I64).unwrap();
+
+    let start = code.find("0d50").unwrap();
+    let edited = format!("{}0d500{}", &code[..start], &code[start + 4..]);
+
+    old_tree.edit(&InputEdit {
+        start_byte: start + 3,
+        old_end_byte: start + 4,
+        new_end_byte: start + 5,
+        start_position: point_at(&code, start + 3),
+        old_end_position: point_at(&code, start + 4),
+        new_end_position: point_at(&edited, start + 5),
+    });
+
+    let tree = mage.parse_edited(&edited, &old_tree).unwrap();
+    let incremental = flatten_incremental(
+        node_kinds,
+        &previous,
+        &old_tree,
+        &tree,
+        &edited,
+        IntWidth::I64,
+    )
+    .unwrap();
+
+    assert_eq!(incremental.reused, 99);
+    assert_eq!(
+        incremental.root,
+        flatten_tree(node_kinds, &tree, &edited, IntWidth::I64).unwrap()
+    );
+}
+
+#[test]
+fn test_incremental_flatten_checks_sources_using_changed_names() {
+    let code = "x : 0d1\ny : { a / x }";
+    let edited = "x : 0d0\ny : { a / x }";
+
+    let mut mage = Mage::new().unwrap();
+    let node_kinds = mage.node_kinds();
+
+    let mut old_tree = mage.parse_text(code).unwrap();
+    let previous = flatten_tree(node_kinds, &old_tree, code, IntWidth::I64).unwrap();
+
+    old_tree.edit(&InputEdit {
+        start_byte: 6,
+        old_end_byte: 7,
+        new_end_byte: 7,
+        start_position: point_at(code, 6),
+        old_end_position: point_at(code, 7),
+        new_end_position: point_at(edited, 7),
+    });
+
+    let tree = mage.parse_edited(edited, &old_tree).unwrap();
+
+    assert_eq!(
+        flatten_incremental(
+            node_kinds,
+            &previous,
+            &old_tree,
+            &tree,
+            edited,
+            IntWidth::I64
+        ),
+        Err(Error::DivisionByZero { span: (14, 19) })
+    );
+}
+
+/// Replaces `code[start..end]` with `replacement` and flattens the result
+/// incrementally and in full.
+fn flatten_edit(
+    code: &str,
+    start: usize,
+    end: usize,
+    replacement: &str,
+) -> (Result<IncrementalFlatten, Error>, Result<FlatRoot, Error>) {
+    let edited = format!("{}{}{}", &code[..start], replacement, &code[end..]);
+
+    let mut mage = Mage::new().unwrap();
+    let node_kinds = mage.node_kinds();
+
+    let mut old_tree = mage.parse_text(code).unwrap();
+    let previous = flatten_tree(node_kinds, &old_tree, code, IntWidth::I64).unwrap();
+
+    old_tree.edit(&InputEdit {
+        start_byte: start,
+        old_end_byte: end,
+        new_end_byte: start + replacement.len(),
+        start_position: point_at(code, start),
+        old_end_position: point_at(code, end),
+        new_end_position: point_at(&edited, start + replacement.len()),
+    });
+
+    let tree = mage.parse_edited(&edited, &old_tree).unwrap();
+
+    (
+        flatten_incremental(
+            node_kinds,
+            &previous,
+            &old_tree,
+            &tree,
+            &edited,
+            IntWidth::I64,
+        ),
+        flatten_tree(node_kinds, &tree, &edited, IntWidth::I64),
+    )
+}
+
+#[test]
+fn test_incremental_flatten_copies_bare_nested_sources() {
+    let (incremental, full) = flatten_edit("e : { { z : 0d3 } }", 0, 0, " ");
+    let incremental = incremental.unwrap();
+
+    assert_eq!(incremental.reused, 2);
+    assert_eq!(incremental.root, full.unwrap());
+}
+
+#[test]
+fn test_incremental_flatten_leaves_out_unused_literals() {
+    let (incremental, full) = flatten_edit("d : { y : 0xFF \"q\" }\nz : 'z'", 26, 27, "x");
+    let root = incremental.unwrap().root;
+    let full = full.unwrap();
+
+    assert_eq!(full.strings.len(), 2);
+    assert_eq!(root.strings.len(), 1);
+    assert_eq!(root.strings[0].value(), "x");
+    assert_eq!(root.sources.len(), full.sources.len());
+}
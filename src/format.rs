@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+use crate::{Error, NodeKinds};
+
+const INDENT: &str = "    ";
+
+/// Prints `tree` in canonical form: one expression per line, sources indented
+/// by four spaces and single spaces around binary operators, except `.`.
+/// Blank lines between expressions are kept, but runs of them collapse into
+/// one. Tokens are kept as written and in order, so the result parses to the
+/// same tree. Programs with syntax errors are refused rather than guessed at.
+pub fn format_tree(node_kinds: &NodeKinds, tree: &Tree, code: &str) -> Result<String, Error> {
+    let root = tree.root_node();
+
+    if root.has_error() {
+        return Err(Error::ParseError(
+            "Error: Cannot format a program with syntax errors.".to_string(),
+        ));
+    }
+
+    let mut output = String::new();
+
+    let mut previous = None;
+
+    for child in root.named_children(&mut root.walk()) {
+        if blank_line_between(code, previous, child) {
+            output.push('\n');
+        }
+
+        format_node(node_kinds, child, code, 0, &mut output);
+        output.push('\n');
+        previous = Some(child);
+    }
+
+    Ok(output)
+}
+
+fn format_node(node_kinds: &NodeKinds, node: Node, code: &str, depth: usize, output: &mut String) {
+    let kind = node.kind_id();
+
+    if kind == node_kinds.source {
+        if node.named_child_count() == 0 {
+            output.push_str("{}");
+            return;
+        }
+
+        output.push_str("{\n");
+
+        let mut previous = None;
+
+        for child in node.named_children(&mut node.walk()) {
+            if blank_line_between(code, previous, child) {
+                output.push('\n');
+            }
+
+            previous = Some(child);
+            output.push_str(&INDENT.repeat(depth + 1));
+            format_node(node_kinds, child, code, depth + 1, output);
+            output.push('\n');
+        }
+
+        output.push_str(&INDENT.repeat(depth));
+        output.push('}');
+    } else if kind == node_kinds.parenthesize {
+        output.push('(');
+
+        if let Some(child) = node.named_child(0) {
+            format_node(node_kinds, child, code, depth, output);
+        }
+
+        output.push(')');
+    } else if [
+        node_kinds.member,
+        node_kinds.call,
+        node_kinds.multiplicative,
+        node_kinds.additive,
+        node_kinds.comparison,
+        node_kinds.logical,
+        node_kinds.assign,
+    ]
+    .contains(&kind)
+    {
+        format_binary(node_kinds, node, code, depth, output);
+    } else {
+        output.push_str(&code[node.byte_range()]);
+    }
+}
+
+fn blank_line_between(code: &str, previous: Option<Node>, next: Node) -> bool {
+    previous.is_some_and(|previous| {
+        code[previous.end_byte()..next.start_byte()]
+            .matches('\n')
+            .count()
+            > 1
+    })
+}
+
+/// Replacement of `span`, a range of the original code, with `replacement`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Edit {
+    pub span: (usize, usize),
+    pub replacement: String,
+}
+
+/// Smallest edit turning `code` into `formatted`: the span of `code` between
+/// their common prefix and suffix, and its replacement. Empty when the two are
+/// equal.
+pub fn format_edits(code: &str, formatted: &str) -> Vec<Edit> {
+    if code == formatted {
+        return Vec::new();
+    }
+
+    let prefix = code
+        .char_indices()
+        .zip(formatted.chars())
+        .find(|((_, one), two)| one != two)
+        .map_or(code.len().min(formatted.len()), |((offset, _), _)| offset);
+
+    let suffix = code[prefix..]
+        .chars()
+        .rev()
+        .zip(formatted[prefix..].chars().rev())
+        .take_while(|(one, two)| one == two)
+        .map(|(one, _)| one.len_utf8())
+        .sum::<usize>();
+
+    vec![Edit {
+        span: (prefix, code.len() - suffix),
+        replacement: formatted[prefix..formatted.len() - suffix].to_string(),
+    }]
+}
+
+/// Operands may be missing on the left, as in `-x` or `=> print`. Signs and
+/// `.` stay attached to their operand.
+fn format_binary(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+    depth: usize,
+    output: &mut String,
+) {
+    let children: Vec<Node> = node.named_children(&mut node.walk()).collect();
+
+    let (one, operator, two) = match children.as_slice() {
+        [one, operator, two] => (Some(*one), *operator, *two),
+        [operator, two] => (None, *operator, *two),
+        _ => {
+            output.push_str(&code[node.byte_range()]);
+            return;
+        }
+    };
+
+    let attached = node.kind_id() == node_kinds.member
+        || (one.is_none() && node.kind_id() == node_kinds.additive);
+
+    if let Some(one) = one {
+        format_node(node_kinds, one, code, depth, output);
+
+        if !attached {
+            output.push(' ');
+        }
+    }
+
+    output.push_str(&code[operator.byte_range()]);
+
+    if !attached {
+        output.push(' ');
+    }
+
+    format_node(node_kinds, two, code, depth, output);
+}
@@ -0,0 +1,101 @@
+use crate::{Edit, Error, Mage, format_edits};
+
+#[test]
+fn test_format_expressions() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.format("a:0d1   b=a+-0d2*( a-b )  a.b=>print").unwrap(),
+        "a : 0d1\nb = a + -0d2 * (a - b)\na.b => print\n"
+    );
+    assert_eq!(mage.format("").unwrap(), "");
+}
+
+#[test]
+fn test_format_sources() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.format("f : { x : 0d1 { } { x => print } }").unwrap(),
+        "f : {\n    x : 0d1\n    {}\n    {\n        x => print\n    }\n}\n"
+    );
+}
+
+#[test]
+fn test_format_blank_lines() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.format("a : 0d1\n\nb : 0d2\n\n\n\nc : 0d3\nd : 0d4")
+            .unwrap(),
+        "a : 0d1\n\nb : 0d2\n\nc : 0d3\nd : 0d4\n"
+    );
+    assert_eq!(
+        mage.format("\n\nf : {\n\n  x : 0d1\n\n\n  x => print\n}\n\n")
+            .unwrap(),
+        "f : {\n    x : 0d1\n\n    x => print\n}\n"
+    );
+}
+
+#[test]
+fn test_format_refuses_syntax_errors() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.format("a : )"),
+        Err(Error::ParseError(
+            "Error: Cannot format a program with syntax errors.".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_format_is_idempotent() {
+    let mut mage = Mage::new().unwrap();
+
+    for code in [
+        "a:0d1 b=a+-0d2*(a-b) a.b=>print",
+        "f : { x : 'a' { y : x } }\n=> f",
+        "a < b <= c && a || b",
+    ] {
+        let once = mage.format(code).unwrap();
+
+        assert_eq!(mage.format(&once).unwrap(), once, "{:?}", code);
+    }
+}
+
+#[test]
+fn test_format_preserves_meaning() {
+    let mut mage = Mage::new().unwrap();
+    let code = "a:0d2 b=(a+0x10)*0d2 {c:'x'=>print} b.c==a||0x10 d:-a<b<=0d3 e:{f:\"\\n\" =>e}";
+
+    let formatted = mage.format(code).unwrap();
+
+    assert_eq!(mage.flatten(&formatted), mage.flatten(code));
+}
+
+#[test]
+fn test_format_edits() {
+    assert_eq!(format_edits("a", "a"), vec![]);
+    assert_eq!(
+        format_edits("a:0d1\n", "a : 0d1\n"),
+        vec![Edit {
+            span: (1, 2),
+            replacement: " : ".to_string()
+        }]
+    );
+    assert_eq!(
+        format_edits("é:x", "é : x"),
+        vec![Edit {
+            span: (2, 3),
+            replacement: " : ".to_string()
+        }]
+    );
+    assert_eq!(
+        format_edits("aa", "aaa"),
+        vec![Edit {
+            span: (2, 2),
+            replacement: "a".to_string()
+        }]
+    );
+}
@@ -1,36 +1,316 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
 use serde::{Deserialize, Serialize};
 use tree_sitter::Language;
 
-use crate::{FlatRoot, Jit, Mage, Stage, compile_root, flatten_tree};
+use crate::{
+    Diagnostic, Edit, ErrorCode, FlatRoot, LineIndex, Mage, Output, Severity, Stage, flatten_tree,
+    format_edits, format_tree, limit_diagnostics, validate_syntax_tree, validate_tree,
+};
+
+#[cfg(feature = "jit")]
+use crate::{Jit, compile_root};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Type {
+    Diagnostics(Vec<Diagnostic>),
     Flat(FlatRoot),
+    #[cfg(feature = "jit")]
     Jit(Jit),
 }
 
+impl Type {
+    /// Writes the result the way `mage run` prints it. `text` is the program
+    /// the result came from, used to give diagnostics line and column numbers.
+    pub fn write(
+        &self,
+        writer: &mut impl Write,
+        output: &Output,
+        text: &str,
+        max_errors: usize,
+    ) -> io::Result<()> {
+        match (output, self) {
+            (Output::Text, Type::Diagnostics(diagnostics)) => {
+                let line_index = LineIndex::new(text);
+
+                for diagnostic in limit_diagnostics(diagnostics.clone(), max_errors) {
+                    writeln!(writer, "{}", diagnostic.render(&line_index))?;
+                }
+
+                Ok(())
+            }
+            (Output::Text, _) => writeln!(writer, "{:#?}", self),
+            (Output::Json, _) => {
+                serde_json::to_writer(&mut *writer, self)?;
+                writeln!(writer)
+            }
+        }
+    }
+}
+
+/// Wall-clock duration of each stage run by `Mage::process_timed`, in the
+/// order they ran.
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    pub stages: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn time<T>(&mut self, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+
+        self.stages.push((stage, start.elapsed()));
+
+        result
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (stage, duration) in &self.stages {
+            writeln!(f, "{}: {:?}", stage, duration)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Error {
     MageError(String),
     ParseError(String),
     FlattenError(String),
+    ValidateError(String),
+    UnsupportedNode {
+        kind: String,
+        span: (usize, usize),
+    },
+    DefinitionWithoutExpression {
+        name: String,
+        span: (usize, usize),
+    },
+    DivisionByZero {
+        span: (usize, usize),
+    },
+    TypeError {
+        message: String,
+        span: (usize, usize),
+    },
+    InvalidEscape {
+        escape: String,
+        span: (usize, usize),
+    },
+    InvalidNumber {
+        text: String,
+        span: (usize, usize),
+    },
+    NumberOutOfRange {
+        text: String,
+        bits: u32,
+        span: (usize, usize),
+    },
+    MissingSyntax {
+        kind: String,
+        span: (usize, usize),
+    },
     JitError(String),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MageError(message)
+            | Error::ParseError(message)
+            | Error::FlattenError(message)
+            | Error::ValidateError(message)
+            | Error::JitError(message)
+            | Error::TypeError { message, .. } => write!(f, "{}", message),
+            Error::UnsupportedNode { kind, .. } => {
+                write!(f, "Error: Cannot process node of unknown type {}.", kind)
+            }
+            Error::DefinitionWithoutExpression { name, .. } => {
+                write!(f, "Error: Definition of `{}` has no expression.", name)
+            }
+            Error::DivisionByZero { .. } => write!(f, "Error: Division by zero."),
+            Error::InvalidEscape { escape, .. } => {
+                write!(f, "Error: Invalid escape `{}` in string literal.", escape)
+            }
+            Error::InvalidNumber { text, .. } => {
+                write!(f, "Error: Invalid number literal `{}`.", text)
+            }
+            Error::NumberOutOfRange { text, bits, .. } => write!(
+                f,
+                "Error: Number literal `{}` does not fit in {} bits.",
+                text, bits
+            ),
+            Error::MissingSyntax { kind, .. } => write!(f, "Error: Missing {}.", kind),
+        }
+    }
+}
+
+impl Error {
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Error::UnsupportedNode { .. } => Some(ErrorCode::UnsupportedNode),
+            Error::DefinitionWithoutExpression { .. } => {
+                Some(ErrorCode::DefinitionWithoutExpression)
+            }
+            Error::DivisionByZero { .. } => Some(ErrorCode::DivisionByZero),
+            Error::TypeError { .. } => Some(ErrorCode::TypeMismatch),
+            Error::InvalidEscape { .. } => Some(ErrorCode::InvalidEscape),
+            Error::InvalidNumber { .. } => Some(ErrorCode::InvalidNumber),
+            Error::NumberOutOfRange { .. } => Some(ErrorCode::NumberOutOfRange),
+            Error::MissingSyntax { .. } => Some(ErrorCode::MissingSyntax),
+            _ => None,
+        }
+    }
+
+    pub fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            Error::UnsupportedNode { span, .. }
+            | Error::DefinitionWithoutExpression { span, .. }
+            | Error::DivisionByZero { span }
+            | Error::TypeError { span, .. }
+            | Error::InvalidEscape { span, .. }
+            | Error::InvalidNumber { span, .. }
+            | Error::NumberOutOfRange { span, .. }
+            | Error::MissingSyntax { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// The same error located at `span`, for errors found in a literal on its
+    /// own that are then reported where the literal is in the program.
+    /// Errors without a location are returned unchanged.
+    pub fn with_span(mut self, span: (usize, usize)) -> Self {
+        match &mut self {
+            Error::UnsupportedNode { span: old, .. }
+            | Error::DefinitionWithoutExpression { span: old, .. }
+            | Error::DivisionByZero { span: old }
+            | Error::TypeError { span: old, .. }
+            | Error::InvalidEscape { span: old, .. }
+            | Error::InvalidNumber { span: old, .. }
+            | Error::NumberOutOfRange { span: old, .. }
+            | Error::MissingSyntax { span: old, .. } => *old = span,
+            _ => {}
+        }
+
+        self
+    }
+}
+
 impl Mage {
     pub fn process(&mut self, stage: &Stage, text: &str) -> Result<Type, Error> {
-        let node_kinds = NodeKinds::new(&self.language);
+        self.process_timed(stage, text, &mut Timings::default())
+    }
+
+    /// Like `process`, recording how long each stage took in `timings`.
+    pub fn process_timed(
+        &mut self,
+        stage: &Stage,
+        text: &str,
+        timings: &mut Timings,
+    ) -> Result<Type, Error> {
+        let node_kinds = self.node_kinds();
+        let tree = timings.time("parse", || self.parse_text(text))?;
+
+        match stage {
+            Stage::Validate => {
+                let linter = self.linter()?;
+
+                Ok(Type::Diagnostics(timings.time("validate", || {
+                    validate_tree(node_kinds, linter, &tree, text, self.int_width)
+                })))
+            }
+            Stage::Flatten => Ok(Type::Flat(timings.time("flatten", || {
+                flatten_tree(node_kinds, &tree, text, self.int_width)
+            })?)),
+            #[cfg(feature = "jit")]
+            Stage::Compile => {
+                let root = timings.time("flatten", || {
+                    flatten_tree(node_kinds, &tree, text, self.int_width)
+                })?;
+
+                Ok(Type::Jit(timings.time("compile", || compile_root(root))?))
+            }
+        }
+    }
+
+    pub fn flatten(&mut self, text: &str) -> Result<FlatRoot, Error> {
+        let node_kinds = self.node_kinds();
         let tree = self.parse_text(text)?;
 
-        let root = flatten_tree(&node_kinds, tree, text)?;
+        flatten_tree(node_kinds, &tree, text, self.int_width)
+    }
 
-        if let Stage::Flatten = stage {
-            return Ok(Type::Flat(root));
+    pub fn validate(&mut self, text: &str) -> Result<Vec<Diagnostic>, Error> {
+        let node_kinds = self.node_kinds();
+        let linter = self.linter()?;
+        let tree = self.parse_text(text)?;
+
+        Ok(validate_tree(
+            node_kinds,
+            linter,
+            &tree,
+            text,
+            self.int_width,
+        ))
+    }
+
+    pub fn format(&mut self, text: &str) -> Result<String, Error> {
+        let node_kinds = self.node_kinds();
+        let tree = self.parse_text(text)?;
+
+        format_tree(node_kinds, &tree, text)
+    }
+
+    /// Edits of `text` that bring it into the form `format` prints.
+    pub fn format_edits(&mut self, text: &str) -> Result<Vec<Edit>, Error> {
+        let formatted = self.format(text)?;
+
+        Ok(format_edits(text, &formatted))
+    }
+
+    /// Runs validate and flatten on `text` and fails unless both accept it.
+    /// When only one of them rejects it, the diagnostics say so, since the
+    /// stages are meant to agree.
+    pub fn accepts(&mut self, text: &str) -> Result<(), Vec<Diagnostic>> {
+        let span = (0, text.len());
+
+        let errors = self
+            .validate(text)
+            .map_err(|error| vec![Diagnostic::from_error(&error, span)])?
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .collect::<Vec<_>>();
+
+        match (errors.is_empty(), self.flatten(text)) {
+            (true, Ok(_)) => Ok(()),
+            (false, Err(_)) => Err(errors),
+            (true, Err(error)) => Err(vec![Diagnostic::error(
+                format!("Error: Flatten rejects what validate accepts: {}", error),
+                error.span().unwrap_or(span),
+            )]),
+            (false, Ok(_)) => Err(errors
+                .into_iter()
+                .chain([Diagnostic::error(
+                    "Error: Validate rejects what flatten accepts.".to_string(),
+                    span,
+                )])
+                .collect()),
         }
+    }
 
-        let jit = compile_root(root)?;
+    pub fn validate_syntax(&mut self, text: &str) -> Result<Vec<Diagnostic>, Error> {
+        let node_kinds = self.node_kinds();
+        let tree = self.parse_text(text)?;
 
-        Ok(Type::Jit(jit))
+        Ok(validate_syntax_tree(node_kinds, &tree, text))
     }
 }
 
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use crate::{FlatExpression, FlatIndex, FlatRoot};
+
+/// Bindings and which of them each binding refers to, directly or from
+/// within a nested source. A binding is a name defined in one source, so the
+/// same name defined in two sources is two nodes, and a reference is resolved
+/// to the binding it sees from the source it is in.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CallGraph {
+    pub names: Vec<String>,
+    /// Source index and identifier index of each binding, in the order of
+    /// `names`.
+    pub bindings: Vec<(usize, usize)>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl CallGraph {
+    /// Whether a binding named `from` refers to a binding named `to`.
+    pub fn has_edge(&self, from: &str, to: &str) -> bool {
+        self.edges
+            .iter()
+            .any(|(one, two)| self.names[*one] == from && self.names[*two] == to)
+    }
+
+    /// Names of the bindings that no binding refers to.
+    pub fn roots(&self) -> Vec<&str> {
+        (0..self.names.len())
+            .filter(|name| !self.edges.iter().any(|(_, two)| two == name))
+            .map(|name| self.names[name].as_str())
+            .collect()
+    }
+
+    /// Graphviz rendering, one line per binding and per edge. Bindings are
+    /// numbered and labelled with their name, since names may repeat.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+
+        for (binding, name) in self.names.iter().enumerate() {
+            dot.push_str(&format!("    {} [label=\"{}\"];\n", binding, name));
+        }
+
+        for (one, two) in &self.edges {
+            dot.push_str(&format!("    {} -> {};\n", one, two));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl FlatRoot {
+    pub fn call_graph(&self) -> CallGraph {
+        let mut parents = HashMap::new();
+        let mut nodes = HashMap::new();
+        let mut names = Vec::new();
+        let mut bindings = Vec::new();
+
+        for (index, source) in self.sources.iter().enumerate() {
+            for expression in &source.expressions {
+                if let FlatExpression::Assign(binary) = expression
+                    && let Some(FlatIndex::Identifier(identifier)) = &binary.one
+                    && !nodes.contains_key(&(index, *identifier))
+                {
+                    nodes.insert((index, *identifier), bindings.len());
+                    names.push(source.identifiers[*identifier].name().to_string());
+                    bindings.push((index, *identifier));
+                }
+
+                for operand in expression.operands() {
+                    if let FlatIndex::Source(nested) = operand {
+                        parents.insert(*nested, index);
+                    }
+                }
+            }
+        }
+
+        // The binding `name` refers to from `source`: the one in the nearest
+        // source around it that defines the name.
+        let resolve = |mut source: usize, name: &str| loop {
+            let binding = self.sources[source]
+                .identifiers
+                .iter()
+                .position(|identifier| identifier.name() == name)
+                .and_then(|identifier| nodes.get(&(source, identifier)));
+
+            match binding {
+                Some(binding) => return Some(*binding),
+                None => source = *parents.get(&source)?,
+            }
+        };
+
+        let mut edges = Vec::new();
+
+        for (index, source) in self.sources.iter().enumerate() {
+            for expression in &source.expressions {
+                if let FlatExpression::Assign(binary) = expression
+                    && let Some(FlatIndex::Identifier(identifier)) = &binary.one
+                {
+                    let one = nodes[&(index, *identifier)];
+                    let mut references = Vec::new();
+                    self.collect_names(index, &binary.two, &mut references);
+
+                    for (source, name) in references {
+                        if let Some(two) = resolve(source, name)
+                            && !edges.contains(&(one, two))
+                        {
+                            edges.push((one, two));
+                        }
+                    }
+                }
+            }
+        }
+
+        CallGraph {
+            names,
+            bindings,
+            edges,
+        }
+    }
+
+    /// Names referred to by `index` in the source `source`, with the source
+    /// each is used in. The name after `.` is a member rather than a
+    /// reference, and a nested source refers to every name used in it,
+    /// including names standing alone as a statement.
+    fn collect_names<'a>(
+        &'a self,
+        source: usize,
+        index: &FlatIndex,
+        names: &mut Vec<(usize, &'a str)>,
+    ) {
+        let flat = &self.sources[source];
+
+        match index {
+            FlatIndex::Identifier(identifier) => {
+                names.push((source, flat.identifiers[*identifier].name()))
+            }
+            FlatIndex::Expression(expression) => match &flat.expressions[*expression] {
+                FlatExpression::Member(binary) => {
+                    if let Some(one) = &binary.one {
+                        self.collect_names(source, one, names);
+                    }
+                }
+                FlatExpression::Assign(binary) => {
+                    self.collect_names(source, &binary.two, names);
+                }
+                FlatExpression::Call(binary)
+                | FlatExpression::Multiplicative(binary)
+                | FlatExpression::Additive(binary)
+                | FlatExpression::Comparison(binary)
+                | FlatExpression::Logical(binary) => {
+                    if let Some(one) = &binary.one {
+                        self.collect_names(source, one, names);
+                    }
+                    self.collect_names(source, &binary.two, names);
+                }
+            },
+            FlatIndex::Source(nested) => {
+                let inner = &self.sources[*nested];
+
+                for expression in 0..inner.expressions.len() {
+                    self.collect_names(*nested, &FlatIndex::Expression(expression), names);
+                }
+
+                // A statement that is only a name, as in `{ a }`, is kept as
+                // an identifier that no expression uses.
+                for (identifier, flat_identifier) in inner.identifiers.iter().enumerate() {
+                    let used = inner.expressions.iter().any(|expression| {
+                        expression
+                            .operands()
+                            .any(|operand| *operand == FlatIndex::Identifier(identifier))
+                    });
+
+                    if !used {
+                        names.push((*nested, flat_identifier.name()));
+                    }
+                }
+            }
+            FlatIndex::Number(_) | FlatIndex::String(_) => {}
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::Mage;
+
+#[test]
+fn test_call_graph_edges() {
+    let mut mage = Mage::new().unwrap();
+    let graph = mage.flatten("a : 0d1 b : a c : b").unwrap().call_graph();
+
+    assert_eq!(graph.names, vec!["a", "b", "c"]);
+    assert!(graph.has_edge("b", "a"));
+    assert!(graph.has_edge("c", "b"));
+    assert_eq!(graph.edges.len(), 2);
+    assert_eq!(graph.roots(), vec!["c"]);
+}
+
+#[test]
+fn test_call_graph_nested_source() {
+    let mut mage = Mage::new().unwrap();
+    let graph = mage
+        .flatten("a : 0d1 f : { x : a => print }")
+        .unwrap()
+        .call_graph();
+
+    assert!(graph.has_edge("f", "a"));
+    assert!(graph.has_edge("x", "a"));
+    assert!(!graph.has_edge("f", "print"));
+}
+
+#[test]
+fn test_call_graph_to_dot() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.flatten("a : 0d1 b : a + a")
+            .unwrap()
+            .call_graph()
+            .to_dot(),
+        "digraph {\n    0 [label=\"a\"];\n    1 [label=\"b\"];\n    1 -> 0;\n}\n"
+    );
+}
+
+#[test]
+fn test_call_graph_keys_bindings_by_source() {
+    let mut mage = Mage::new().unwrap();
+    let graph = mage
+        .flatten("a : 0d1\nf : { a : 0d2\nx : a }\ng : a")
+        .unwrap()
+        .call_graph();
+
+    assert_eq!(graph.names, vec!["a", "x", "a", "f", "g"]);
+    assert_eq!(graph.bindings[0].0, 0);
+    assert_eq!(graph.bindings[2].0, 1);
+    assert_eq!(graph.edges, vec![(1, 0), (3, 0), (4, 2)]);
+}
+
+#[test]
+fn test_call_graph_names_standing_alone() {
+    let mut mage = Mage::new().unwrap();
+    let graph = mage.flatten("a : { b }\nb : 0d1").unwrap().call_graph();
+
+    assert!(graph.has_edge("a", "b"));
+}
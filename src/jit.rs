@@ -1,14 +1,110 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{Error, FlatRoot};
+use crate::{
+    CallGraph, Error, FlatBinary, FlatExpression, FlatIndex, FlatOperator, FlatRoot, FlatSource,
+};
 
 pub fn compile_root(root: FlatRoot) -> Result<Jit, Error> {
-    _ = root;
+    check_definitions(&root)?;
+
     Err(Error::JitError(
         "Error: JIT compilation is not implemented.".to_string(),
     ))
 }
 
+/// Rejects constant definitions that depend on themselves, directly, through
+/// other constants or from within nested sources. Names resolve to the
+/// binding they see, as in `FlatRoot::call_graph`. Forward references are
+/// fine as long as they do not form a cycle.
+fn check_definitions(root: &FlatRoot) -> Result<(), Error> {
+    let graph = root.call_graph();
+    let constants: Vec<bool> = graph
+        .bindings
+        .iter()
+        .map(|(source, identifier)| is_constant(&root.sources[*source], *identifier))
+        .collect();
+
+    let mut dependencies = vec![Vec::new(); graph.names.len()];
+
+    for (one, two) in &graph.edges {
+        if constants[*one] && constants[*two] {
+            dependencies[*one].push(*two);
+        }
+    }
+
+    let mut states = vec![VisitState::Unvisited; graph.names.len()];
+    let mut path = Vec::new();
+
+    for binding in 0..dependencies.len() {
+        find_cycle(&graph, &dependencies, &mut states, &mut path, binding)?;
+    }
+
+    Ok(())
+}
+
+/// Whether `identifier` is defined with `:` in `source`.
+fn is_constant(source: &FlatSource, identifier: usize) -> bool {
+    source.expressions.iter().any(|expression| {
+        matches!(
+            expression,
+            FlatExpression::Assign(FlatBinary {
+                one: Some(FlatIndex::Identifier(one)),
+                operator: FlatOperator::Constant,
+                ..
+            }) if *one == identifier
+        )
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VisitState {
+    Unvisited,
+    Visiting,
+    Visited,
+}
+
+fn find_cycle(
+    graph: &CallGraph,
+    dependencies: &[Vec<usize>],
+    states: &mut [VisitState],
+    path: &mut Vec<usize>,
+    binding: usize,
+) -> Result<(), Error> {
+    match states[binding] {
+        VisitState::Visited => return Ok(()),
+        VisitState::Visiting => {
+            let start = path
+                .iter()
+                .position(|current| *current == binding)
+                .unwrap_or(0);
+
+            let names: Vec<&str> = path[start..]
+                .iter()
+                .chain(std::iter::once(&binding))
+                .map(|current| graph.names[*current].as_str())
+                .collect();
+
+            return Err(Error::JitError(format!(
+                "Error: Circular definition: {}.",
+                names.join(" -> ")
+            )));
+        }
+        VisitState::Unvisited => {}
+    }
+
+    states[binding] = VisitState::Visiting;
+    path.push(binding);
+
+    for dependency in &dependencies[binding] {
+        find_cycle(graph, dependencies, states, path, *dependency)?;
+    }
+
+    path.pop();
+    states[binding] = VisitState::Visited;
+
+    Ok(())
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Jit {
     //
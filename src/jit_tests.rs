@@ -0,0 +1,61 @@
+use crate::{Error, Mage, compile_root};
+
+#[test]
+fn test_self_reference() {
+    let root = Mage::new().unwrap().flatten("a : a").unwrap();
+
+    assert_eq!(
+        compile_root(root),
+        Err(Error::JitError(
+            "Error: Circular definition: a -> a.".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_two_node_cycle() {
+    let root = Mage::new().unwrap().flatten("a : b + 0d1\nb : a").unwrap();
+
+    assert_eq!(
+        compile_root(root),
+        Err(Error::JitError(
+            "Error: Circular definition: a -> b -> a.".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_self_reference_in_nested_source() {
+    let root = Mage::new().unwrap().flatten("a : {a}").unwrap();
+
+    assert_eq!(
+        compile_root(root),
+        Err(Error::JitError(
+            "Error: Circular definition: a -> a.".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_cycle_through_nested_sources() {
+    let root = Mage::new().unwrap().flatten("a : {b}\nb : {a}").unwrap();
+
+    assert_eq!(
+        compile_root(root),
+        Err(Error::JitError(
+            "Error: Circular definition: a -> b -> a.".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_forward_reference() {
+    let root = Mage::new().unwrap().flatten("a : b\nb : 0d1").unwrap();
+
+    assert_eq!(
+        compile_root(root),
+        Err(Error::JitError(
+            "Error: JIT compilation is not implemented.".to_string()
+        ))
+    );
+}
@@ -7,14 +7,111 @@ pub use cli::*;
 mod general;
 pub use general::*;
 
+#[cfg(feature = "lsp")]
 mod ls;
+#[cfg(feature = "lsp")]
 pub use ls::*;
 
+mod pool;
+pub use pool::*;
+
+mod line_index;
+pub use line_index::*;
+
+mod diagnostic;
+pub use diagnostic::*;
+
+mod number;
+pub use number::*;
+
+mod string;
+pub use string::*;
+
+mod validate;
+pub use validate::*;
+
 mod flatten;
 pub use flatten::*;
 
+mod constant;
+pub(crate) use constant::*;
+
+mod types;
+pub(crate) use types::*;
+
+mod scope;
+pub(crate) use scope::*;
+
+mod format;
+pub use format::*;
+
+mod lint;
+pub use lint::*;
+
+mod tokens;
+pub use tokens::*;
+
+mod visit;
+pub use visit::*;
+
+#[cfg(feature = "jit")]
 mod jit;
+#[cfg(feature = "jit")]
 pub use jit::*;
 
+mod build;
+pub use build::*;
+
+mod explain;
+pub use explain::*;
+
+mod graph;
+pub use graph::*;
+
+mod watch;
+pub use watch::*;
+
+#[cfg(test)]
+mod mage_tests;
+
 #[cfg(test)]
 mod flatten_tests;
+
+#[cfg(all(test, feature = "jit"))]
+mod jit_tests;
+
+#[cfg(test)]
+mod validate_tests;
+
+#[cfg(test)]
+mod visit_tests;
+
+#[cfg(test)]
+mod tokens_tests;
+
+#[cfg(test)]
+mod number_tests;
+
+#[cfg(test)]
+mod string_tests;
+
+#[cfg(test)]
+mod build_tests;
+
+#[cfg(test)]
+mod pool_tests;
+
+#[cfg(test)]
+mod explain_tests;
+
+#[cfg(test)]
+mod format_tests;
+
+#[cfg(test)]
+mod lint_tests;
+
+#[cfg(test)]
+mod graph_tests;
+
+#[cfg(test)]
+mod watch_tests;
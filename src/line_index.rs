@@ -0,0 +1,69 @@
+use tree_sitter::Point;
+
+pub struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let mut line_starts = vec![0];
+
+        for (offset, byte) in text.bytes().enumerate() {
+            if byte == b'\n' {
+                line_starts.push(offset + 1);
+            }
+        }
+
+        Self { text, line_starts }
+    }
+
+    /// Zero-based line and UTF-16 column of a byte offset, as the language
+    /// server protocol counts them.
+    pub fn position(&self, offset: usize) -> (u32, u32) {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+
+        let column = self.text[self.line_starts[line]..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum::<usize>();
+
+        (line as u32, column as u32)
+    }
+
+    /// Zero-based row and byte column of a byte offset, as tree-sitter counts
+    /// them.
+    pub fn point(&self, offset: usize) -> Point {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+
+        Point::new(line, offset - self.line_starts[line])
+    }
+
+    /// Byte offset of a zero-based line and UTF-16 column. Positions past the
+    /// end of a line or of the text are clamped.
+    pub fn offset(&self, line: u32, column: u32) -> usize {
+        let Some(start) = self.line_starts.get(line as usize) else {
+            return self.text.len();
+        };
+
+        let end = self
+            .line_starts
+            .get(line as usize + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+
+        let mut remaining = column as usize;
+
+        for (offset, character) in self.text[*start..end].char_indices() {
+            if remaining == 0 || character == '\n' {
+                return start + offset;
+            }
+
+            remaining = remaining.saturating_sub(character.len_utf16());
+        }
+
+        end
+    }
+}
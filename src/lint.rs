@@ -0,0 +1,332 @@
+use std::cmp::Reverse;
+
+use tree_sitter::{Language, Node, Query, QueryCursor, StreamingIterator, Tree};
+
+use crate::{Diagnostic, Edit, Error, NodeKinds, Severity, lookup, uses_of};
+
+const ZERO_DIVISOR: &str = r#"
+(multiplicative
+  [(divide) (modulo)]
+  .
+  [(binary) (octal) (decimal) (hex)] @divisor
+  (#match? @divisor "^0[bBoOdDxX][0_]+$"))
+"#;
+
+const SELF_ASSIGNMENT: &str = r#"
+(assign
+  .
+  (identifier) @target
+  (variable)
+  .
+  (identifier) @value
+  (#eq? @target @value))
+"#;
+
+/// Parentheses directly around other parentheses or around a single name,
+/// literal or string, none of which they can regroup.
+const REDUNDANT_PARENTHESES: &str = r#"
+(parenthesize . (parenthesize) .) @redundant
+
+(parenthesize
+  .
+  [(identifier) (binary) (octal) (decimal) (hex) (single_quoted) (double_quoted)]
+  .) @redundant
+"#;
+
+/// Names that are not numbers, so that literals lexed as identifiers are
+/// left to validation. `{}` is replaced by the style's pattern.
+const NAMING: &str = r#"
+((identifier) @name
+  (#not-match? @name "^[0-9]")
+  (#not-match? @name "{}"))
+"#;
+
+/// Suggested replacement for the text of a rule's first capture.
+type Suggest = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Lint reported wherever a tree-sitter query matches. The diagnostic spans
+/// the first capture of the match, and `{}` in the message is replaced by its
+/// text.
+pub struct Rule {
+    pub name: String,
+    pub message: String,
+    pub severity: Severity,
+    query: Query,
+    suggest: Option<Suggest>,
+    /// Whether the suggestion renames a binding, so every use of it has to
+    /// change along with the capture.
+    renames: bool,
+    /// Whether matches inside another match of the rule are left out, so that
+    /// their fixes do not overlap.
+    outermost: bool,
+}
+
+/// Spelling required of names by the `naming` rule.
+#[derive(Debug, PartialEq, Clone)]
+pub enum NamingStyle {
+    SnakeCase,
+    CamelCase,
+    /// Regular expression every name has to match, without suggestions.
+    Pattern(String),
+}
+
+impl NamingStyle {
+    pub fn pattern(&self) -> &str {
+        match self {
+            NamingStyle::SnakeCase => "^[a-z_][a-z0-9_]*$",
+            NamingStyle::CamelCase => "^[a-z][a-zA-Z0-9]*$",
+            NamingStyle::Pattern(pattern) => pattern,
+        }
+    }
+
+    /// `name` respelled in this style, or `None` when there is no spelling to
+    /// suggest.
+    pub fn suggest(&self, name: &str) -> Option<String> {
+        match self {
+            NamingStyle::SnakeCase => {
+                let mut suggestion = String::new();
+                let mut previous: Option<char> = None;
+
+                for character in name.chars() {
+                    if character.is_uppercase()
+                        && previous.is_some_and(|previous| {
+                            previous.is_lowercase() || previous.is_ascii_digit()
+                        })
+                    {
+                        suggestion.push('_');
+                    }
+
+                    suggestion.extend(character.to_lowercase());
+                    previous = Some(character);
+                }
+
+                Some(suggestion)
+            }
+            NamingStyle::CamelCase => {
+                let mut parts = name.split('_').filter(|part| !part.is_empty());
+                let mut suggestion = parts.next()?.to_lowercase();
+
+                for part in parts {
+                    let mut characters = part.chars();
+
+                    if let Some(first) = characters.next() {
+                        suggestion.extend(first.to_uppercase());
+                        suggestion.push_str(&characters.as_str().to_lowercase());
+                    }
+                }
+
+                Some(suggestion)
+            }
+            NamingStyle::Pattern(_) => None,
+        }
+    }
+}
+
+impl Rule {
+    pub fn new(
+        language: &Language,
+        name: &str,
+        query: &str,
+        message: &str,
+        severity: Severity,
+    ) -> Result<Self, Error> {
+        let query = Query::new(language, query).map_err(|error| {
+            Error::MageError(format!(
+                "Error: Invalid query for rule `{}`: {}.",
+                name, error
+            ))
+        })?;
+
+        Ok(Self {
+            name: name.to_string(),
+            message: message.to_string(),
+            severity,
+            query,
+            suggest: None,
+            renames: false,
+            outermost: false,
+        })
+    }
+
+    /// Reports every name not spelled in `style`, with a fix renaming it and
+    /// every other use of its binding when the style can suggest a spelling
+    /// that is not already taken.
+    pub fn naming(language: &Language, style: NamingStyle) -> Result<Self, Error> {
+        let pattern = style.pattern().replace('\\', "\\\\").replace('"', "\\\"");
+        let message = match &style {
+            NamingStyle::SnakeCase => "Information: `{}` is not snake_case.".to_string(),
+            NamingStyle::CamelCase => "Information: `{}` is not camelCase.".to_string(),
+            NamingStyle::Pattern(pattern) => {
+                format!("Information: `{{}}` does not match `{}`.", pattern)
+            }
+        };
+
+        let rule = Self::new(
+            language,
+            "naming",
+            &NAMING.replace("{}", &pattern),
+            &message,
+            Severity::Information,
+        )?;
+
+        Ok(Self {
+            renames: true,
+            ..rule.with_suggestion(move |name| style.suggest(name))
+        })
+    }
+
+    /// Offers the suggested replacement of the first capture as a fix.
+    pub fn with_suggestion(
+        mut self,
+        suggest: impl Fn(&str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.suggest = Some(Box::new(suggest));
+        self
+    }
+
+    /// Reports only matches that are not inside another match of this rule.
+    pub fn outermost(mut self) -> Self {
+        self.outermost = true;
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct Linter {
+    rules: Vec<Rule>,
+}
+
+impl Linter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Linter with the rules that ship with Mage.
+    pub fn with_default_rules(language: &Language) -> Result<Self, Error> {
+        let mut linter = Self::new();
+
+        linter.add_rule(Rule::new(
+            language,
+            "zero-divisor",
+            ZERO_DIVISOR,
+            "Error: Division by zero.",
+            Severity::Error,
+        )?);
+        linter.add_rule(Rule::new(
+            language,
+            "self-assignment",
+            SELF_ASSIGNMENT,
+            "Warning: `{}` is assigned to itself.",
+            Severity::Warning,
+        )?);
+        linter.add_rule(
+            Rule::new(
+                language,
+                "redundant-parentheses",
+                REDUNDANT_PARENTHESES,
+                "Warning: `{}` has redundant parentheses.",
+                Severity::Warning,
+            )?
+            .with_suggestion(|text| Some(text[1..text.len() - 1].trim().to_string()))
+            .outermost(),
+        );
+
+        Ok(linter)
+    }
+
+    pub fn add_rule(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Diagnostics of every rule, in source order.
+    pub fn lint(&self, node_kinds: &NodeKinds, tree: &Tree, code: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut cursor = QueryCursor::new();
+
+        for rule in &self.rules {
+            let mut captured = Vec::new();
+            let mut matches = cursor.matches(&rule.query, tree.root_node(), code.as_bytes());
+
+            while let Some(found) = matches.next() {
+                if let Some(capture) = found.captures.first() {
+                    captured.push(capture.node);
+                }
+            }
+
+            if rule.outermost {
+                captured.sort_by_key(|node| (node.start_byte(), Reverse(node.end_byte())));
+                captured.dedup_by(|inner, outer| outer.end_byte() >= inner.end_byte());
+            }
+
+            for node in captured {
+                let span = (node.start_byte(), node.end_byte());
+                let text = &code[span.0..span.1];
+                let message = rule.message.replace("{}", text);
+
+                let mut diagnostic = Diagnostic {
+                    severity: rule.severity.clone(),
+                    ..Diagnostic::error(message, span)
+                };
+
+                if let Some(suggestion) = rule
+                    .suggest
+                    .as_ref()
+                    .and_then(|suggest| suggest(text))
+                    .filter(|suggestion| suggestion != text)
+                {
+                    if !rule.renames {
+                        diagnostic = diagnostic.with_fix(
+                            format!("Replace with `{}`", suggestion),
+                            span,
+                            suggestion,
+                        );
+                    } else if let Some(edits) =
+                        rename_edits(node_kinds, tree, code, node, &suggestion)
+                    {
+                        diagnostic =
+                            diagnostic.with_edits(format!("Rename to `{}`", suggestion), edits);
+                    }
+                }
+
+                diagnostics.push(diagnostic);
+            }
+        }
+
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span);
+
+        diagnostics
+    }
+}
+
+/// Edits renaming `identifier` and every other use of its binding to
+/// `new_name`, or `None` when `new_name` is already visible at one of them and
+/// the rename would capture it.
+fn rename_edits(
+    node_kinds: &NodeKinds,
+    tree: &Tree,
+    code: &str,
+    identifier: Node,
+    new_name: &str,
+) -> Option<Vec<Edit>> {
+    let uses = uses_of(node_kinds, tree, code, identifier);
+
+    if uses
+        .iter()
+        .any(|node| lookup(node_kinds, code, *node, new_name).is_some())
+    {
+        return None;
+    }
+
+    Some(
+        uses.into_iter()
+            .map(|node| Edit {
+                span: (node.start_byte(), node.end_byte()),
+                replacement: new_name.to_string(),
+            })
+            .collect(),
+    )
+}
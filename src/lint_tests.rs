@@ -0,0 +1,186 @@
+use crate::{Edit, Fix, Linter, Mage, NamingStyle, Rule, Severity};
+
+fn lint(linter: &Linter, code: &str) -> Vec<(String, (usize, usize))> {
+    let mut mage = Mage::new().unwrap();
+    let tree = mage.parse_text(code).unwrap();
+
+    linter
+        .lint(mage.node_kinds(), &tree, code)
+        .into_iter()
+        .map(|diagnostic| (diagnostic.message, diagnostic.span))
+        .collect()
+}
+
+#[test]
+fn test_custom_rule() {
+    let mage = Mage::new().unwrap();
+    let mut linter = Linter::new();
+
+    linter.add_rule(
+        Rule::new(
+            &mage.language,
+            "decimal",
+            "(decimal) @literal",
+            "Information: Decimal literal `{}`.",
+            Severity::Information,
+        )
+        .unwrap(),
+    );
+
+    assert_eq!(
+        lint(&linter, "a : 0d1 + 0d2\nb : 0x3 * 0d4"),
+        vec![
+            ("Information: Decimal literal `0d1`.".to_string(), (4, 7)),
+            ("Information: Decimal literal `0d2`.".to_string(), (10, 13)),
+            ("Information: Decimal literal `0d4`.".to_string(), (24, 27)),
+        ]
+    );
+}
+
+#[test]
+fn test_default_rules() {
+    let mage = Mage::new().unwrap();
+    let linter = Linter::with_default_rules(&mage.language).unwrap();
+
+    assert_eq!(
+        lint(&linter, "a / 0d0\na % 0x00\na / 0d10\nx = x\nx = y"),
+        vec![
+            ("Error: Division by zero.".to_string(), (4, 7)),
+            ("Error: Division by zero.".to_string(), (12, 16)),
+            ("Warning: `x` is assigned to itself.".to_string(), (26, 27)),
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_query() {
+    let mage = Mage::new().unwrap();
+
+    assert!(
+        Rule::new(
+            &mage.language,
+            "broken",
+            "(no_such_node) @x",
+            "",
+            Severity::Hint,
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_naming_rule() {
+    let mage = Mage::new().unwrap();
+    let mut linter = Linter::new();
+
+    linter.add_rule(Rule::naming(&mage.language, NamingStyle::SnakeCase).unwrap());
+
+    let mut parser = Mage::new().unwrap();
+    let code = "myVar : 0d1\nmy_var : myVar + 0d1_000";
+    let tree = parser.parse_text(code).unwrap();
+    let diagnostics = linter.lint(parser.node_kinds(), &tree, code);
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].severity, Severity::Information);
+    assert_eq!(
+        diagnostics[0].message,
+        "Information: `myVar` is not snake_case."
+    );
+    // Renaming `myVar` would make it refer to `my_var` instead, whether the
+    // fix is offered at the definition or at the use.
+    assert!(diagnostics[0].fixes.is_empty());
+    assert!(diagnostics[1].fixes.is_empty());
+
+    let code = "myVar : 0d1\ny : myVar + 0d1\nf : { myVar : 0d2 }";
+    let tree = parser.parse_text(code).unwrap();
+    let diagnostics = linter.lint(parser.node_kinds(), &tree, code);
+
+    assert_eq!(diagnostics.len(), 3);
+    assert_eq!(
+        diagnostics[1].fixes,
+        vec![Fix {
+            title: "Rename to `my_var`".to_string(),
+            edits: vec![
+                Edit {
+                    span: (0, 5),
+                    replacement: "my_var".to_string(),
+                },
+                Edit {
+                    span: (16, 21),
+                    replacement: "my_var".to_string(),
+                },
+            ],
+        }]
+    );
+    assert_eq!(diagnostics[2].fixes[0].edits.len(), 1);
+
+    assert!(lint(&linter, "my_var : 0d1").is_empty());
+}
+
+#[test]
+fn test_naming_styles() {
+    assert_eq!(
+        NamingStyle::CamelCase.suggest("my_var_name"),
+        Some("myVarName".to_string())
+    );
+    assert_eq!(
+        NamingStyle::SnakeCase.suggest("parseHTTP2Request"),
+        Some("parse_http2_request".to_string())
+    );
+
+    let mage = Mage::new().unwrap();
+    let mut linter = Linter::new();
+
+    linter.add_rule(
+        Rule::naming(
+            &mage.language,
+            NamingStyle::Pattern("^[a-z]{1,3}$".to_string()),
+        )
+        .unwrap(),
+    );
+
+    assert_eq!(
+        lint(&linter, "abc : 0d1\nabcd : abc"),
+        vec![(
+            "Information: `abcd` does not match `^[a-z]{1,3}$`.".to_string(),
+            (10, 14)
+        )]
+    );
+}
+
+#[test]
+fn test_redundant_parentheses() {
+    let mut mage = Mage::new().unwrap();
+    let linter = Linter::with_default_rules(&mage.language).unwrap();
+
+    let code = "x : ((0d1))\ny : (x + 0d1) * 0d2";
+    let tree = mage.parse_text(code).unwrap();
+    let diagnostics = linter.lint(mage.node_kinds(), &tree, code);
+
+    // Only the outer pair, so that the fixes do not overlap.
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "Warning: `((0d1))` has redundant parentheses."
+    );
+    assert_eq!(
+        diagnostics[0].fixes,
+        vec![Fix {
+            title: "Replace with `(0d1)`".to_string(),
+            edits: vec![Edit {
+                span: (4, 11),
+                replacement: "(0d1)".to_string(),
+            }],
+        }]
+    );
+}
+
+#[test]
+fn test_validate_lints() {
+    let mut mage = Mage::new().unwrap();
+    let diagnostics = mage.validate("x : (0d1)\nx => print").unwrap();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert_eq!(diagnostics[0].span, (4, 9));
+}
@@ -1,28 +1,630 @@
-use tower_lsp_server::jsonrpc::Result;
-use tower_lsp_server::lsp_types::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use hashbrown::HashMap;
+use tower_lsp_server::jsonrpc::{self, Result};
+use tower_lsp_server::lsp_types::{self, *};
 use tower_lsp_server::{Client, LanguageServer};
 
+use tree_sitter::{Node, Tree};
+
+use crate::{
+    DEFAULT_MAX_ERRORS, Diagnostic, Document, Error, IntWidth, LineIndex, MagePool, NodeKinds,
+    Severity, TokenKind, constant_values, definition_of, flatten_incremental, flatten_tree,
+    is_malformed_number, is_number_literal, limit_diagnostics, uses_of, validate_syntax_tree,
+    validate_tree_with,
+};
+
 #[derive(Debug, Clone)]
 pub struct Backend {
     pub client: Client,
+    pub documents: Arc<Mutex<HashMap<Uri, Document>>>,
+    pub pool: Arc<MagePool>,
+    /// Whether the client lets the server register for file changes.
+    pub watch_files: Arc<AtomicBool>,
+}
+
+impl Backend {
+    pub fn new(client: Client) -> Self {
+        Self::with_int_width(client, IntWidth::default())
+    }
+
+    /// Server checking numbers at `int_width`, as `--int-width` asks.
+    pub fn with_int_width(client: Client, int_width: IntWidth) -> Self {
+        Self {
+            client,
+            documents: Arc::new(Mutex::new(HashMap::new())),
+            pool: Arc::new(MagePool::with_int_width(int_width)),
+            watch_files: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Full validation of an open document, run on the blocking thread pool.
+    /// Parsing and validation are CPU-bound and would otherwise hold up every
+    /// other handler sharing the executor for the length of a large file.
+    pub async fn analyze(&self, uri: &Uri) -> Option<Vec<lsp_types::Diagnostic>> {
+        self.diagnose(uri, document_diagnostics)
+            .await
+            .map(|(diagnostics, _)| diagnostics)
+    }
+
+    /// Runs `task` on the blocking thread pool with a snapshot of the
+    /// document, so that neither the executor nor the lock on the documents
+    /// is held while it parses. Edits arriving meanwhile apply to the store,
+    /// not to the snapshot.
+    async fn with_document<T: Send + 'static>(
+        &self,
+        uri: &Uri,
+        task: impl FnOnce(&MagePool, Document) -> T + Send + 'static,
+    ) -> Option<T> {
+        let document = self.documents.lock().unwrap().get(uri)?.clone();
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || task(&pool, document))
+            .await
+            .ok()
+    }
+
+    /// Runs `diagnose` on a snapshot of the document and keeps the trees it
+    /// parsed. Returns the version diagnosed.
+    async fn diagnose(
+        &self,
+        uri: &Uri,
+        diagnose: fn(&MagePool, &mut Document) -> Vec<lsp_types::Diagnostic>,
+    ) -> Option<(Vec<lsp_types::Diagnostic>, i32)> {
+        let (diagnostics, snapshot) = self
+            .with_document(uri, move |pool, mut document| {
+                (diagnose(pool, &mut document), document)
+            })
+            .await?;
+
+        let version = snapshot.version;
+        self.store_trees(uri, snapshot);
+
+        Some((diagnostics, version))
+    }
+
+    /// Remembers the trees parsed for `snapshot`, so that the next version is
+    /// parsed and flattened incrementally, unless the document changed since
+    /// the snapshot was taken.
+    fn store_trees(&self, uri: &Uri, snapshot: Document) {
+        if let Some(document) = self.documents.lock().unwrap().get_mut(uri)
+            && document.version == snapshot.version
+        {
+            document.tree = snapshot.tree;
+            document.flat = snapshot.flat;
+        }
+    }
+
+    /// Remembers the tokens computed on `snapshot` for the next delta request,
+    /// unless the document changed since the snapshot was taken.
+    fn store_semantic_tokens(&self, uri: &Uri, snapshot: Document) {
+        if let Some(document) = self.documents.lock().unwrap().get_mut(uri)
+            && document.version == snapshot.version
+        {
+            document.semantic_tokens = snapshot.semantic_tokens;
+        }
+    }
+
+    async fn publish_diagnostics(
+        &self,
+        uri: Uri,
+        diagnose: fn(&MagePool, &mut Document) -> Vec<lsp_types::Diagnostic>,
+    ) {
+        if let Some((diagnostics, version)) = self.diagnose(&uri, diagnose).await {
+            self.client
+                .publish_diagnostics(uri, diagnostics, Some(version))
+                .await;
+        }
+    }
+}
+
+/// Full validation, run when a document is opened or saved. Flattens from
+/// the root of the last flatten when the document has one, copying the
+/// sources no change touched, and keeps the new root for the next time.
+pub fn document_diagnostics(
+    pool: &MagePool,
+    document: &mut Document,
+) -> Vec<lsp_types::Diagnostic> {
+    let diagnostics = document_tree(pool, document).and_then(|tree| {
+        let text = &document.text;
+        let previous = &document.flat;
+
+        let (diagnostics, root) = pool.with(|mage| {
+            let node_kinds = mage.node_kinds();
+            let int_width = mage.int_width;
+
+            Ok(validate_tree_with(
+                node_kinds,
+                mage.linter()?,
+                &tree,
+                text,
+                int_width,
+                // Any error is reported by flattening in full, which also
+                // recovers from a previous root that no longer fits.
+                || match previous {
+                    Some((old_tree, root)) => {
+                        flatten_incremental(node_kinds, root, old_tree, &tree, text, int_width)
+                            .map(|flattened| flattened.root)
+                            .or_else(|_| flatten_tree(node_kinds, &tree, text, int_width))
+                    }
+                    None => flatten_tree(node_kinds, &tree, text, int_width),
+                },
+            ))
+        })?;
+
+        if let Some(root) = root {
+            document.flat = Some((tree, root));
+        }
+
+        Ok(diagnostics)
+    });
+
+    lsp_diagnostics(document, diagnostics)
+}
+
+/// Syntax-only validation, run on every change.
+pub fn syntax_diagnostics(pool: &MagePool, document: &mut Document) -> Vec<lsp_types::Diagnostic> {
+    let diagnostics = document_tree(pool, document).and_then(|tree| {
+        pool.with(|mage| {
+            Ok(validate_syntax_tree(
+                mage.node_kinds(),
+                &tree,
+                &document.text,
+            ))
+        })
+    });
+
+    lsp_diagnostics(document, diagnostics)
+}
+
+/// Tree of the document, parsed incrementally from the tree of the last
+/// parse when there is one, which it then replaces.
+fn document_tree(pool: &MagePool, document: &mut Document) -> std::result::Result<Tree, Error> {
+    let tree = match &document.tree {
+        Some(old_tree) => pool.with(|mage| mage.parse_edited(&document.text, old_tree))?,
+        None => pool.parse(&document.text)?,
+    };
+
+    document.tree = Some(tree.clone());
+
+    Ok(tree)
+}
+
+fn lsp_diagnostics(
+    document: &Document,
+    diagnostics: std::result::Result<Vec<Diagnostic>, Error>,
+) -> Vec<lsp_types::Diagnostic> {
+    let diagnostics = diagnostics
+        .unwrap_or_else(|error| vec![Diagnostic::from_error(&error, (0, document.text.len()))]);
+
+    let line_index = LineIndex::new(&document.text);
+
+    limit_diagnostics(diagnostics, DEFAULT_MAX_ERRORS)
+        .iter()
+        .map(|diagnostic| lsp_diagnostic(diagnostic, &line_index))
+        .collect()
+}
+
+/// Open documents whose files changed on disk, in the order of `changes`.
+/// The client owns their text, so they are validated again from the document
+/// store rather than from disk, replacing diagnostics that may still be from
+/// the syntax-only pass. Mage has no imports, so files that are not open
+/// affect no open document and are left alone.
+pub fn watched_files(documents: &HashMap<Uri, Document>, changes: Vec<FileEvent>) -> Vec<Uri> {
+    let mut uris = Vec::new();
+
+    for change in changes {
+        if documents.contains_key(&change.uri) && !uris.contains(&change.uri) {
+            uris.push(change.uri);
+        }
+    }
+
+    uris
+}
+
+/// Edits that bring a document into canonical form, run before it is saved.
+/// Documents with syntax errors are left alone.
+pub fn document_format_edits(pool: &MagePool, document: &Document) -> Vec<TextEdit> {
+    let Ok(edits) = pool.format_edits(&document.text) else {
+        return Vec::new();
+    };
+
+    let line_index = LineIndex::new(&document.text);
+
+    edits
+        .into_iter()
+        .map(|edit| TextEdit::new(lsp_range(&line_index, edit.span), edit.replacement))
+        .collect()
+}
+
+/// Semantic tokens of a document, or of the part of it within `range`. Each
+/// token is positioned relative to the one before it, and the first relative
+/// to the start of the document, as the protocol requires.
+pub fn document_semantic_tokens(
+    pool: &MagePool,
+    document: &Document,
+    range: Option<Range>,
+) -> Vec<SemanticToken> {
+    let line_index = LineIndex::new(&document.text);
+
+    let span = match range {
+        Some(range) => (
+            line_index.offset(range.start.line, range.start.character),
+            line_index.offset(range.end.line, range.end.character),
+        ),
+        None => (0, document.text.len()),
+    };
+
+    let Ok(tokens) = pool.tokens_range(&document.text, span) else {
+        return Vec::new();
+    };
+
+    let mut previous = (0, 0);
+
+    tokens
+        .iter()
+        .map(|token| {
+            let (line, column) = line_index.position(token.span.0);
+            let length = document.text[token.span.0..token.span.1]
+                .chars()
+                .map(char::len_utf16)
+                .sum::<usize>();
+
+            let delta_start = if line == previous.0 {
+                column - previous.1
+            } else {
+                column
+            };
+
+            let semantic_token = SemanticToken {
+                delta_line: line - previous.0,
+                delta_start,
+                length: length as u32,
+                token_type: match token.kind {
+                    TokenKind::Identifier => 0,
+                    TokenKind::String => 1,
+                    TokenKind::Number => 2,
+                    TokenKind::Operator => 3,
+                },
+                token_modifiers_bitset: 0,
+            };
+
+            previous = (line, column);
+            semantic_token
+        })
+        .collect()
+}
+
+/// All semantic tokens of a document, remembered so that the next request can
+/// be answered with a delta. The document version serves as result id.
+pub fn document_semantic_tokens_full(pool: &MagePool, document: &mut Document) -> SemanticTokens {
+    let tokens = SemanticTokens {
+        result_id: Some(document.version.to_string()),
+        data: document_semantic_tokens(pool, document, None),
+    };
+
+    document.semantic_tokens = Some(tokens.clone());
+
+    tokens
+}
+
+/// Changes since the tokens sent as `previous_result_id`, or all tokens when
+/// those are no longer known.
+pub fn document_semantic_tokens_delta(
+    pool: &MagePool,
+    document: &mut Document,
+    previous_result_id: &str,
+) -> SemanticTokensFullDeltaResult {
+    let previous = document
+        .semantic_tokens
+        .take()
+        .filter(|previous| previous.result_id.as_deref() == Some(previous_result_id));
+
+    let tokens = document_semantic_tokens_full(pool, document);
+
+    match previous {
+        Some(previous) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+            result_id: tokens.result_id,
+            edits: semantic_tokens_edits(&previous.data, &tokens.data),
+        }),
+        None => SemanticTokensFullDeltaResult::Tokens(tokens),
+    }
+}
+
+/// Single edit replacing the tokens between the common prefix and suffix of
+/// `previous` and `current`. Offsets count integers, five per token.
+fn semantic_tokens_edits(
+    previous: &[SemanticToken],
+    current: &[SemanticToken],
+) -> Vec<SemanticTokensEdit> {
+    if previous == current {
+        return Vec::new();
+    }
+
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(one, two)| one == two)
+        .count();
+
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(one, two)| one == two)
+        .count();
+
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: ((previous.len() - prefix - suffix) * 5) as u32,
+        data: Some(current[prefix..current.len() - suffix].to_vec()),
+    }]
+}
+
+/// Command of the value lenses, which shows the constant and its value as a
+/// message, e.g. for copying.
+pub const SHOW_VALUE_COMMAND: &str = "mage.showValue";
+
+/// Lens above each top-level constant whose value is known at compile time,
+/// showing that value. Running it shows `name = value` as a message.
+pub fn document_code_lenses(pool: &MagePool, document: &Document) -> Vec<CodeLens> {
+    let Ok(values) = pool.with_tree(&document.text, |mage, tree| {
+        Ok(constant_values(
+            mage.node_kinds(),
+            tree.root_node(),
+            &document.text,
+            mage.int_width,
+        ))
+    }) else {
+        return Vec::new();
+    };
+
+    let line_index = LineIndex::new(&document.text);
+
+    values
+        .into_iter()
+        .map(|(span, name, value)| CodeLens {
+            range: lsp_range(&line_index, span),
+            command: Some(Command::new(
+                format!("= {}", value),
+                SHOW_VALUE_COMMAND.to_string(),
+                Some(vec![LSPAny::String(format!("{} = {}", name, value))]),
+            )),
+            data: None,
+        })
+        .collect()
+}
+
+/// Range of the name under the cursor, or `None` when there is nothing there
+/// that can be renamed.
+pub fn document_prepare_rename(
+    pool: &MagePool,
+    document: &Document,
+    position: Position,
+) -> Option<Range> {
+    let line_index = LineIndex::new(&document.text);
+    let offset = line_index.offset(position.line, position.character);
+
+    let span = pool
+        .with_tree(&document.text, |mage, tree| {
+            let node_kinds = mage.node_kinds();
+
+            Ok(identifier_at(node_kinds, tree, &document.text, offset)
+                .map(|node| (node.start_byte(), node.end_byte())))
+        })
+        .ok()??;
+
+    Some(lsp_range(&line_index, span))
+}
+
+/// Edits renaming the name under the cursor to `new_name`, together with
+/// every other use of the same binding, or `None` when the cursor is not on a
+/// name. Names that are never bound are renamed wherever they are unbound.
+pub fn document_rename(
+    pool: &MagePool,
+    document: &Document,
+    position: Position,
+    new_name: &str,
+) -> Option<Vec<TextEdit>> {
+    let line_index = LineIndex::new(&document.text);
+    let offset = line_index.offset(position.line, position.character);
+    let code = &document.text;
+
+    let spans = pool
+        .with_tree(code, |mage, tree| {
+            let node_kinds = mage.node_kinds();
+
+            let Some(target) = identifier_at(node_kinds, tree, code, offset) else {
+                return Ok(None);
+            };
+
+            Ok(Some(
+                uses_of(node_kinds, tree, code, target)
+                    .into_iter()
+                    .map(|node| (node.start_byte(), node.end_byte()))
+                    .collect::<Vec<_>>(),
+            ))
+        })
+        .ok()??;
+
+    Some(
+        spans
+            .into_iter()
+            .map(|span| TextEdit::new(lsp_range(&line_index, span), new_name.to_string()))
+            .collect(),
+    )
+}
+
+/// Whether `name` parses as a single identifier rather than a number.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|character| character.is_alphanumeric() || character == '_')
+        && !is_number_literal(name)
+        && !is_malformed_number(name)
+}
+
+/// Identifier at or just before `offset`, so that a cursor at the end of a
+/// name still finds it. Words that are numbers, well-formed or not, are not
+/// names.
+fn identifier_at<'tree>(
+    node_kinds: &NodeKinds,
+    tree: &'tree Tree,
+    code: &str,
+    offset: usize,
+) -> Option<Node<'tree>> {
+    [Some(offset), offset.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .find_map(|offset| {
+            let node = tree.root_node().descendant_for_byte_range(offset, offset)?;
+            let text = &code[node.byte_range()];
+
+            (node.kind_id() == node_kinds.identifier
+                && !is_number_literal(text)
+                && !is_malformed_number(text))
+            .then_some(node)
+        })
+}
+
+/// Range of the definition of the name under the cursor, or `None` when it is
+/// not a name or is never bound.
+pub fn document_definition(
+    pool: &MagePool,
+    document: &Document,
+    position: Position,
+) -> Option<Range> {
+    let line_index = LineIndex::new(&document.text);
+    let offset = line_index.offset(position.line, position.character);
+
+    let span = pool
+        .with_tree(&document.text, |mage, tree| {
+            let node_kinds = mage.node_kinds();
+
+            Ok(identifier_at(node_kinds, tree, &document.text, offset)
+                .and_then(|node| definition_of(node_kinds, &document.text, node))
+                .map(|node| (node.start_byte(), node.end_byte())))
+        })
+        .ok()??;
+
+    Some(lsp_range(&line_index, span))
+}
+
+pub fn document_code_actions(
+    pool: &MagePool,
+    uri: &Uri,
+    document: &Document,
+    range: Range,
+) -> CodeActionResponse {
+    let Ok(diagnostics) = pool.validate(&document.text) else {
+        return Vec::new();
+    };
+
+    let line_index = LineIndex::new(&document.text);
+    let start = line_index.offset(range.start.line, range.start.character);
+    let end = line_index.offset(range.end.line, range.end.character);
+
+    let mut actions = Vec::new();
+
+    for diagnostic in diagnostics {
+        if diagnostic.span.1 < start || diagnostic.span.0 > end {
+            continue;
+        }
+
+        for fix in &diagnostic.fixes {
+            let edits = fix
+                .edits
+                .iter()
+                .map(|edit| {
+                    TextEdit::new(lsp_range(&line_index, edit.span), edit.replacement.clone())
+                })
+                .collect();
+
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: fix.title.clone(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![lsp_diagnostic(&diagnostic, &line_index)]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some([(uri.clone(), edits)].into_iter().collect()),
+                    ..WorkspaceEdit::default()
+                }),
+                ..CodeAction::default()
+            }));
+        }
+    }
+
+    actions
+}
+
+pub fn lsp_diagnostic(diagnostic: &Diagnostic, line_index: &LineIndex) -> lsp_types::Diagnostic {
+    let severity = match diagnostic.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Information => DiagnosticSeverity::INFORMATION,
+        Severity::Hint => DiagnosticSeverity::HINT,
+    };
+
+    lsp_types::Diagnostic {
+        range: lsp_range(line_index, diagnostic.span),
+        severity: Some(severity),
+        code: diagnostic
+            .code
+            .map(|code| NumberOrString::String(code.code().to_string())),
+        source: Some("mage".to_string()),
+        message: diagnostic.message.clone(),
+        ..lsp_types::Diagnostic::default()
+    }
+}
+
+pub fn lsp_range(line_index: &LineIndex, span: (usize, usize)) -> Range {
+    let (start_line, start_character) = line_index.position(span.0);
+    let (end_line, end_character) = line_index.position(span.1);
+
+    Range::new(
+        Position::new(start_line, start_character),
+        Position::new(end_line, end_character),
+    )
 }
 
 impl LanguageServer for Backend {
-    async fn initialize(&self, _initialize_params: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, initialize_params: InitializeParams) -> Result<InitializeResult> {
+        let watch_files = initialize_params
+            .capabilities
+            .workspace
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+
+        self.watch_files.store(watch_files, Ordering::Relaxed);
+
         let initialize_result = InitializeResult {
             server_info: Some(ServerInfo {
                 name: "mage".to_string(),
                 version: None,
             }),
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::INCREMENTAL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        will_save_wait_until: Some(true),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..TextDocumentSyncOptions::default()
+                    },
                 )),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(false),
                     ..Default::default()
                 }),
-                execute_command_provider: None,
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![SHOW_VALUE_COMMAND.to_string()],
+                    ..ExecuteCommandOptions::default()
+                }),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
                 workspace: Some(WorkspaceServerCapabilities {
                     workspace_folders: Some(WorkspaceFoldersServerCapabilities {
                         supported: Some(true),
@@ -55,14 +657,19 @@ impl LanguageServer for Backend {
                                     token_modifiers: vec![],
                                 },
                                 range: Some(true),
-                                full: Some(SemanticTokensFullOptions::Bool(true)),
+                                full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
                             },
                             static_registration_options: StaticRegistrationOptions::default(),
                         },
                     ),
                 ),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                })),
                 ..ServerCapabilities::default()
             },
             offset_encoding: None,
@@ -75,6 +682,27 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "initialized")
             .await;
+
+        if !self.watch_files.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.mage".to_string()),
+                kind: None,
+            }],
+        };
+
+        let registration = Registration {
+            id: "mage-watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            self.client.log_message(MessageType::ERROR, err).await;
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
@@ -93,24 +721,36 @@ impl LanguageServer for Backend {
             .await;
     }
 
-    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
         self.client
             .log_message(MessageType::INFO, "did_change_watched_files")
             .await;
+
+        let uris = watched_files(&self.documents.lock().unwrap(), params.changes);
+
+        for uri in uris {
+            self.publish_diagnostics(uri, document_diagnostics).await;
+        }
     }
 
-    async fn execute_command(&self, _: ExecuteCommandParams) -> Result<Option<LSPAny>> {
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<LSPAny>> {
         self.client
             .log_message(MessageType::INFO, "execute_command")
             .await;
 
-        match self.client.apply_edit(WorkspaceEdit::default()).await {
-            Ok(res) if res.applied => self.client.log_message(MessageType::INFO, "applied").await,
-            Ok(_) => self.client.log_message(MessageType::INFO, "rejected").await,
-            Err(err) => self.client.log_message(MessageType::ERROR, err).await,
-        }
+        match (params.command.as_str(), params.arguments.first()) {
+            (SHOW_VALUE_COMMAND, Some(LSPAny::String(message))) => {
+                self.client
+                    .show_message(MessageType::INFO, message.clone())
+                    .await;
 
-        Ok(None)
+                Ok(None)
+            }
+            (command, _) => Err(jsonrpc::Error::invalid_params(format!(
+                "Error: Unknown command `{}`.",
+                command
+            ))),
+        }
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
@@ -120,6 +760,15 @@ impl LanguageServer for Backend {
                 format!("did_open: {}", params.text_document.uri.to_string()),
             )
             .await;
+
+        let uri = params.text_document.uri;
+
+        self.documents.lock().unwrap().insert(
+            uri.clone(),
+            Document::new(params.text_document.text, params.text_document.version),
+        );
+
+        self.publish_diagnostics(uri, document_diagnostics).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
@@ -129,6 +778,39 @@ impl LanguageServer for Backend {
                 format!("did_change: {}", params.text_document.uri.to_string()),
             )
             .await;
+
+        let uri = params.text_document.uri;
+
+        if let Some(document) = self.documents.lock().unwrap().get_mut(&uri) {
+            for change in params.content_changes {
+                document.apply_change(change);
+            }
+
+            document.version = params.text_document.version;
+        }
+
+        self.publish_diagnostics(uri, syntax_diagnostics).await;
+    }
+
+    async fn will_save_wait_until(
+        &self,
+        params: WillSaveTextDocumentParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        self.client
+            .log_message(
+                MessageType::INFO,
+                format!(
+                    "will_save_wait_until: {}",
+                    params.text_document.uri.as_str()
+                ),
+            )
+            .await;
+
+        Ok(self
+            .with_document(&params.text_document.uri, |pool, document| {
+                document_format_edits(pool, &document)
+            })
+            .await)
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
@@ -138,6 +820,9 @@ impl LanguageServer for Backend {
                 format!("did_save: {}", params.text_document.uri.to_string()),
             )
             .await;
+
+        self.publish_diagnostics(params.text_document.uri, document_diagnostics)
+            .await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
@@ -147,6 +832,11 @@ impl LanguageServer for Backend {
                 format!("did_close: {}", params.text_document.uri.to_string()),
             )
             .await;
+
+        let uri = params.text_document.uri;
+
+        self.documents.lock().unwrap().remove(&uri);
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
     }
 
     async fn goto_definition(
@@ -167,20 +857,17 @@ impl LanguageServer for Backend {
             )
             .await;
 
-        // let uri = params.text_document_position_params.text_document.uri;
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
 
-        // let start_position = params.text_document_position_params.position;
-        // let end_position = Position {
-        //     line: start_position.line,
-        //     character: start_position.character + 1,
-        // };
+        let range = self
+            .with_document(&uri, move |pool, document| {
+                document_definition(pool, &document, position)
+            })
+            .await
+            .flatten();
 
-        // Ok(Some(GotoDefinitionResponse::Scalar(Location {
-        //     uri: uri,
-        //     range: Range::new(start_position, end_position),
-        // })))
-
-        Ok(None)
+        Ok(range.map(|range| GotoDefinitionResponse::Scalar(Location { uri, range })))
     }
 
     async fn completion(&self, _: CompletionParams) -> Result<Option<CompletionResponse>> {
@@ -197,23 +884,154 @@ impl LanguageServer for Backend {
         Ok(None)
     }
 
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        self.client
+            .log_message(MessageType::INFO, "prepare_rename")
+            .await;
+
+        let range = self
+            .with_document(&params.text_document.uri, move |pool, document| {
+                document_prepare_rename(pool, &document, params.position)
+            })
+            .await;
+
+        match range {
+            Some(Some(range)) => Ok(Some(PrepareRenameResponse::Range(range))),
+            Some(None) => Err(jsonrpc::Error::invalid_params(
+                "Error: Only names can be renamed.",
+            )),
+            None => Ok(None),
+        }
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        self.client.log_message(MessageType::INFO, "rename").await;
+
+        if !is_valid_name(&params.new_name) {
+            return Err(jsonrpc::Error::invalid_params(format!(
+                "Error: `{}` is not a valid name.",
+                params.new_name
+            )));
+        }
+
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let edits = self
+            .with_document(&uri, move |pool, document| {
+                document_rename(pool, &document, position, &params.new_name)
+            })
+            .await;
+
+        match edits {
+            Some(Some(edits)) => Ok(Some(WorkspaceEdit {
+                changes: Some([(uri, edits)].into_iter().collect()),
+                ..WorkspaceEdit::default()
+            })),
+            Some(None) => Err(jsonrpc::Error::invalid_params(
+                "Error: Only names can be renamed.",
+            )),
+            None => Ok(None),
+        }
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        self.client
+            .log_message(MessageType::INFO, "code_action")
+            .await;
+
+        let uri = params.text_document.uri;
+
+        Ok(self
+            .with_document(&uri, {
+                let uri = uri.clone();
+
+                move |pool, document| document_code_actions(pool, &uri, &document, params.range)
+            })
+            .await)
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        self.client
+            .log_message(MessageType::INFO, "code_lens")
+            .await;
+
+        Ok(self
+            .with_document(&params.text_document.uri, |pool, document| {
+                document_code_lenses(pool, &document)
+            })
+            .await)
+    }
+
     async fn semantic_tokens_full(
         &self,
-        _: SemanticTokensParams,
+        params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
         self.client
             .log_message(MessageType::INFO, "semantic_tokens_full")
             .await;
-        Ok(None)
+
+        let uri = params.text_document.uri;
+
+        let Some((tokens, snapshot)) = self
+            .with_document(&uri, |pool, mut document| {
+                (document_semantic_tokens_full(pool, &mut document), document)
+            })
+            .await
+        else {
+            return Ok(None);
+        };
+
+        self.store_semantic_tokens(&uri, snapshot);
+
+        Ok(Some(SemanticTokensResult::Tokens(tokens)))
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        self.client
+            .log_message(MessageType::INFO, "semantic_tokens_full_delta")
+            .await;
+
+        let uri = params.text_document.uri;
+
+        let Some((tokens, snapshot)) = self
+            .with_document(&uri, move |pool, mut document| {
+                let tokens =
+                    document_semantic_tokens_delta(pool, &mut document, &params.previous_result_id);
+
+                (tokens, document)
+            })
+            .await
+        else {
+            return Ok(None);
+        };
+
+        self.store_semantic_tokens(&uri, snapshot);
+
+        Ok(Some(tokens))
     }
 
     async fn semantic_tokens_range(
         &self,
-        _: SemanticTokensRangeParams,
+        params: SemanticTokensRangeParams,
     ) -> Result<Option<SemanticTokensRangeResult>> {
         self.client
             .log_message(MessageType::INFO, "semantic_tokens_range")
             .await;
-        Ok(None)
+
+        Ok(self
+            .with_document(&params.text_document.uri, move |pool, document| {
+                SemanticTokensRangeResult::Tokens(SemanticTokens {
+                    result_id: None,
+                    data: document_semantic_tokens(pool, &document, Some(params.range)),
+                })
+            })
+            .await)
     }
 }
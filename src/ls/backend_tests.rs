@@ -0,0 +1,510 @@
+use std::sync::Mutex;
+
+use hashbrown::HashMap;
+use tower_lsp_server::lsp_types::{
+    CodeActionOrCommand, DiagnosticSeverity, FileChangeType, FileEvent, LSPAny, NumberOrString,
+    PartialResultParams, Position, Range, SemanticToken, SemanticTokensDelta, SemanticTokensEdit,
+    SemanticTokensFullDeltaResult, SemanticTokensParams, SemanticTokensResult,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextEdit, Uri, WorkDoneProgressParams,
+};
+use tower_lsp_server::{LanguageServer, LspService};
+
+use crate::{
+    Backend, Diagnostic, Document, IntWidth, LineIndex, MagePool, SHOW_VALUE_COMMAND,
+    document_code_actions, document_code_lenses, document_definition, document_diagnostics,
+    document_format_edits, document_prepare_rename, document_rename, document_semantic_tokens,
+    document_semantic_tokens_delta, document_semantic_tokens_full, lsp_diagnostic,
+    syntax_diagnostics, watched_files,
+};
+
+#[test]
+fn test_save_reports_deep_diagnostics() {
+    let pool = MagePool::new();
+    let mut document = Document::new("a : 0d1".to_string(), 1);
+
+    assert!(syntax_diagnostics(&pool, &mut document).is_empty());
+
+    let diagnostics = document_diagnostics(&pool, &mut document);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    assert_eq!(diagnostics[0].message, "Warning: Unused variable `a`.");
+
+    let mut document = Document::new("x : 0d0\ny : 0d1 / x\ny => print".to_string(), 2);
+
+    assert!(syntax_diagnostics(&pool, &mut document).is_empty());
+
+    let diagnostics = document_diagnostics(&pool, &mut document);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(diagnostics[0].message, "Error: Division by zero.");
+}
+
+#[test]
+fn test_change_reports_syntax_errors() {
+    let pool = MagePool::new();
+    let mut document = Document::new("a : )".to_string(), 1);
+
+    assert_eq!(syntax_diagnostics(&pool, &mut document).len(), 1);
+    assert_eq!(
+        document_diagnostics(&pool, &mut document),
+        syntax_diagnostics(&pool, &mut document)
+    );
+}
+
+#[test]
+fn test_number_code_action() {
+    let uri: Uri = "file:///number.mage".parse().unwrap();
+    let pool = MagePool::new();
+    let document = Document::new("x : 0b12\nx => f".to_string(), 1);
+
+    let range = Range::new(Position::new(0, 5), Position::new(0, 5));
+    let actions = document_code_actions(&pool, &uri, &document, range);
+
+    let titles: Vec<String> = actions
+        .iter()
+        .map(|action| match action {
+            CodeActionOrCommand::CodeAction(action) => action.title.clone(),
+            CodeActionOrCommand::Command(command) => command.title.clone(),
+        })
+        .collect();
+
+    assert_eq!(
+        titles,
+        vec![
+            "Replace with `0o12`",
+            "Replace with `0d12`",
+            "Replace with `0x12`"
+        ]
+    );
+
+    let CodeActionOrCommand::CodeAction(action) = &actions[1] else {
+        panic!("Expected a code action");
+    };
+
+    let edits = &action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+
+    assert_eq!(
+        edits,
+        &vec![TextEdit::new(
+            Range::new(Position::new(0, 4), Position::new(0, 8)),
+            "0d12".to_string()
+        )]
+    );
+}
+
+#[test]
+fn test_format_on_save() {
+    let pool = MagePool::new();
+    let document = Document::new("a : 0d1\na=>print\n".to_string(), 1);
+
+    assert_eq!(
+        document_format_edits(&pool, &document),
+        vec![TextEdit::new(
+            Range::new(Position::new(1, 1), Position::new(1, 3)),
+            " => ".to_string()
+        )]
+    );
+
+    let formatted = Document::new("a : 0d1\na => print\n".to_string(), 2);
+
+    assert!(document_format_edits(&pool, &formatted).is_empty());
+}
+
+#[test]
+fn test_definition_of_call_and_member_targets() {
+    let pool = MagePool::new();
+    let document = Document::new(
+        "f : { b : 0d1 }\nresult : (0d2 => f)\nf.b\ng".to_string(),
+        1,
+    );
+
+    assert_eq!(
+        document_definition(&pool, &document, Position::new(1, 17)),
+        Some(Range::new(Position::new(0, 0), Position::new(0, 1)))
+    );
+    assert_eq!(
+        document_definition(&pool, &document, Position::new(2, 2)),
+        Some(Range::new(Position::new(0, 6), Position::new(0, 7)))
+    );
+    assert_eq!(
+        document_definition(&pool, &document, Position::new(3, 0)),
+        None
+    );
+}
+
+#[test]
+fn test_rename_binding() {
+    let pool = MagePool::new();
+    let document = Document::new(
+        "x : 0d1\nf : { x : 0d2\nx => print }\nx => print".to_string(),
+        1,
+    );
+
+    let edits = document_rename(&pool, &document, Position::new(3, 0), "y").unwrap();
+    let ranges: Vec<Range> = edits.iter().map(|edit| edit.range).collect();
+
+    assert_eq!(
+        ranges,
+        vec![
+            Range::new(Position::new(0, 0), Position::new(0, 1)),
+            Range::new(Position::new(3, 0), Position::new(3, 1)),
+        ]
+    );
+    assert!(edits.iter().all(|edit| edit.new_text == "y"));
+
+    assert_eq!(
+        document_rename(&pool, &document, Position::new(0, 2), "y"),
+        None
+    );
+}
+
+#[test]
+fn test_prepare_rename() {
+    let pool = MagePool::new();
+    let document = Document::new("x : 0d1\nx => print".to_string(), 1);
+
+    let name = Some(Range::new(Position::new(0, 0), Position::new(0, 1)));
+
+    assert_eq!(
+        document_prepare_rename(&pool, &document, Position::new(0, 0)),
+        name
+    );
+    assert_eq!(
+        document_prepare_rename(&pool, &document, Position::new(0, 1)),
+        name
+    );
+    assert_eq!(
+        document_prepare_rename(&pool, &document, Position::new(0, 2)),
+        None
+    );
+    assert_eq!(
+        document_prepare_rename(&pool, &document, Position::new(0, 5)),
+        None
+    );
+}
+
+#[test]
+fn test_watched_file_change_updates_diagnostics() {
+    let pool = MagePool::new();
+    let open: Uri = "file:///open.mage".parse().unwrap();
+    let closed: Uri = "file:///closed.mage".parse().unwrap();
+
+    let documents: HashMap<Uri, Document> =
+        [(open.clone(), Document::new("a : 0d1".to_string(), 1))]
+            .into_iter()
+            .collect();
+
+    let uris = watched_files(
+        &documents,
+        vec![
+            FileEvent::new(open.clone(), FileChangeType::CHANGED),
+            FileEvent::new(closed, FileChangeType::CHANGED),
+            FileEvent::new(open.clone(), FileChangeType::DELETED),
+        ],
+    );
+
+    assert_eq!(uris, vec![open.clone()]);
+
+    let diagnostics = document_diagnostics(&pool, &mut documents[&open].clone());
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "Warning: Unused variable `a`.");
+}
+
+#[test]
+fn test_semantic_tokens_range() {
+    let pool = MagePool::new();
+    let document = Document::new("a : 0d1\nb : 'x'\nc : a".to_string(), 1);
+
+    assert_eq!(document_semantic_tokens(&pool, &document, None).len(), 9);
+
+    let range = Range::new(Position::new(1, 0), Position::new(1, 7));
+
+    assert_eq!(
+        document_semantic_tokens(&pool, &document, Some(range)),
+        vec![
+            SemanticToken {
+                delta_line: 1,
+                delta_start: 0,
+                length: 1,
+                token_type: 0,
+                token_modifiers_bitset: 0,
+            },
+            SemanticToken {
+                delta_line: 0,
+                delta_start: 2,
+                length: 1,
+                token_type: 3,
+                token_modifiers_bitset: 0,
+            },
+            SemanticToken {
+                delta_line: 0,
+                delta_start: 2,
+                length: 3,
+                token_type: 1,
+                token_modifiers_bitset: 0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_semantic_tokens_delta() {
+    let pool = MagePool::new();
+    let mut document = Document::new("a : 0d1\nb : a\nc : b".to_string(), 1);
+
+    let full = document_semantic_tokens_full(&pool, &mut document);
+
+    assert_eq!(full.result_id, Some("1".to_string()));
+    assert_eq!(full.data.len(), 9);
+
+    document.apply_change(TextDocumentContentChangeEvent {
+        range: Some(Range::new(Position::new(1, 2), Position::new(1, 3))),
+        range_length: None,
+        text: "=".to_string(),
+    });
+    document.version = 2;
+
+    assert_eq!(
+        document_semantic_tokens_delta(&pool, &mut document, "1"),
+        SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+            result_id: Some("2".to_string()),
+            edits: Vec::new(),
+        })
+    );
+
+    document.apply_change(TextDocumentContentChangeEvent {
+        range: Some(Range::new(Position::new(1, 4), Position::new(1, 5))),
+        range_length: None,
+        text: "0d22".to_string(),
+    });
+    document.version = 3;
+
+    assert_eq!(
+        document_semantic_tokens_delta(&pool, &mut document, "2"),
+        SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+            result_id: Some("3".to_string()),
+            edits: vec![SemanticTokensEdit {
+                start: 25,
+                delete_count: 5,
+                data: Some(vec![SemanticToken {
+                    delta_line: 0,
+                    delta_start: 2,
+                    length: 4,
+                    token_type: 2,
+                    token_modifiers_bitset: 0,
+                }]),
+            }],
+        })
+    );
+
+    assert!(matches!(
+        document_semantic_tokens_delta(&pool, &mut document, "1"),
+        SemanticTokensFullDeltaResult::Tokens(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_analyze_does_not_block_other_handlers() {
+    let (service, _) = LspService::new(Backend::new);
+    let backend = service.inner();
+
+    let uri: Uri = "file:///large.mage".parse().unwrap();
+    let text = "a : 0d1 + 0d2 * 0d3\na => print\n".repeat(10_000);
+
+    backend
+        .documents
+        .lock()
+        .unwrap()
+        .insert(uri.clone(), Document::new(text, 1));
+
+    let order = Mutex::new(Vec::new());
+
+    let (diagnostics, _) = tokio::join!(
+        async {
+            let diagnostics = backend.analyze(&uri).await;
+            order.lock().unwrap().push("analyze");
+            diagnostics
+        },
+        async {
+            order.lock().unwrap().push("other");
+        }
+    );
+
+    assert_eq!(diagnostics, Some(Vec::new()));
+    assert_eq!(*order.lock().unwrap(), vec!["other", "analyze"]);
+
+    let order = Mutex::new(Vec::new());
+    let params = SemanticTokensParams {
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+        text_document: TextDocumentIdentifier::new(uri.clone()),
+    };
+
+    let (tokens, _) = tokio::join!(
+        async {
+            let tokens = backend.semantic_tokens_full(params).await;
+            order.lock().unwrap().push("tokens");
+            tokens
+        },
+        async {
+            order.lock().unwrap().push("other");
+        }
+    );
+
+    let Ok(Some(SemanticTokensResult::Tokens(tokens))) = tokens else {
+        panic!("Expected semantic tokens");
+    };
+
+    assert_eq!(tokens.data.len(), 10_000 * 10);
+    assert_eq!(*order.lock().unwrap(), vec!["other", "tokens"]);
+    assert_eq!(
+        backend.documents.lock().unwrap()[&uri].semantic_tokens,
+        Some(tokens)
+    );
+}
+
+#[tokio::test]
+async fn test_diagnostics_flatten_incrementally() {
+    let (service, _) = LspService::new(Backend::new);
+    let backend = service.inner();
+
+    let uri: Uri = "file:///incremental.mage".parse().unwrap();
+    let text: String = (0..100)
+        .map(|index| format!("a{} : {{ b : 0d{} }}\n", index, index))
+        .collect();
+
+    backend
+        .documents
+        .lock()
+        .unwrap()
+        .insert(uri.clone(), Document::new(text, 1));
+
+    backend.analyze(&uri).await.unwrap();
+
+    {
+        let mut documents = backend.documents.lock().unwrap();
+        let document = documents.get_mut(&uri).unwrap();
+
+        assert!(document.flat.is_some());
+
+        document.apply_change(TextDocumentContentChangeEvent {
+            range: Some(Range::new(Position::new(50, 14), Position::new(50, 16))),
+            range_length: None,
+            text: "7".to_string(),
+        });
+        document.version = 2;
+
+        let (tree, _) = document.flat.as_ref().unwrap();
+
+        assert!(tree.root_node().has_changes());
+    }
+
+    backend.analyze(&uri).await.unwrap();
+
+    let document = backend.documents.lock().unwrap()[&uri].clone();
+    let (tree, root) = document.flat.unwrap();
+
+    assert!(!tree.root_node().has_changes());
+    assert_eq!(root, backend.pool.flatten(&document.text).unwrap());
+    assert!(document.text.contains("a50 : { b : 0d7 }"));
+}
+
+#[test]
+fn test_diagnostics_after_edit_before_bare_nested_source() {
+    let pool = MagePool::new();
+    let mut document = Document::new("e : { { z : 0d3 } }\ne => print".to_string(), 1);
+
+    let diagnostics = document_diagnostics(&pool, &mut document);
+
+    document.apply_change(TextDocumentContentChangeEvent {
+        range: Some(Range::new(Position::new(0, 0), Position::new(0, 0))),
+        range_length: None,
+        text: " ".to_string(),
+    });
+    document.version = 2;
+
+    let shifted = document_diagnostics(&pool, &mut document);
+
+    assert_eq!(shifted.len(), diagnostics.len());
+    assert!(
+        shifted
+            .iter()
+            .zip(&diagnostics)
+            .all(|(shifted, diagnostic)| shifted.message == diagnostic.message)
+    );
+    assert_eq!(
+        document.flat.as_ref().unwrap().1,
+        pool.flatten(&document.text).unwrap()
+    );
+}
+
+#[test]
+fn test_code_lens_values() {
+    let pool = MagePool::new();
+    let document = Document::new("x : 0d4 + 0d1\ny = x\nz : x * 0d2\nw : y".to_string(), 1);
+
+    let lenses: Vec<(u32, String)> = document_code_lenses(&pool, &document)
+        .into_iter()
+        .map(|lens| (lens.range.start.line, lens.command.unwrap().title))
+        .collect();
+
+    assert_eq!(
+        lenses,
+        vec![(0, "= 5".to_string()), (2, "= 10".to_string())]
+    );
+
+    let command = document_code_lenses(&pool, &document)[0]
+        .command
+        .clone()
+        .unwrap();
+
+    assert_eq!(command.command, SHOW_VALUE_COMMAND);
+    assert_eq!(
+        command.arguments,
+        Some(vec![LSPAny::String("x = 5".to_string())])
+    );
+}
+
+#[test]
+fn test_code_lens_values_wrap_at_int_width() {
+    let pool = MagePool::with_int_width(IntWidth::I32);
+    let document = Document::new("x : 0x7FFF_FFFF + 0d1".to_string(), 1);
+
+    assert_eq!(
+        document_code_lenses(&pool, &document)[0]
+            .command
+            .as_ref()
+            .unwrap()
+            .title,
+        "= -2147483648"
+    );
+
+    let document = Document::new("a : 0d3000000000".to_string(), 1);
+
+    assert!(document_code_lenses(&pool, &document).is_empty());
+    assert!(!document_diagnostics(&pool, &mut document.clone()).is_empty());
+}
+
+#[test]
+fn test_flatten_error_converts_to_lsp_diagnostic() {
+    let code = "a : 0d1\nb : a / 0d0";
+    let pool = MagePool::new();
+
+    let error = pool.flatten(code).unwrap_err();
+    let diagnostic = Diagnostic::from_error(&error, (0, code.len()));
+    let converted = lsp_diagnostic(&diagnostic, &LineIndex::new(code));
+
+    assert_eq!(
+        converted.range,
+        Range::new(Position::new(1, 4), Position::new(1, 11))
+    );
+    assert_eq!(converted.severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(
+        converted.code,
+        Some(NumberOrString::String("E0007".to_string()))
+    );
+    assert_eq!(converted.message, "Error: Division by zero.");
+}
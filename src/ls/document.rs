@@ -0,0 +1,72 @@
+use tower_lsp_server::lsp_types::{SemanticTokens, TextDocumentContentChangeEvent};
+use tree_sitter::{InputEdit, Tree};
+
+use crate::{FlatRoot, LineIndex};
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub text: String,
+    pub version: i32,
+    /// Tokens last sent to the client, which delta requests are relative to.
+    pub semantic_tokens: Option<SemanticTokens>,
+    /// Tree of the last parse, with the changes since applied, so that the
+    /// next parse can reuse it.
+    pub tree: Option<Tree>,
+    /// Tree and root of the last flatten, with the changes since applied to
+    /// the tree, so that the next flatten can reuse unchanged sources.
+    pub flat: Option<(Tree, FlatRoot)>,
+}
+
+impl Document {
+    pub fn new(text: String, version: i32) -> Self {
+        Self {
+            text,
+            version,
+            semantic_tokens: None,
+            tree: None,
+            flat: None,
+        }
+    }
+
+    pub fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let line_index = LineIndex::new(&self.text);
+
+                let start = line_index.offset(range.start.line, range.start.character);
+                let end = line_index
+                    .offset(range.end.line, range.end.character)
+                    .max(start);
+
+                let start_position = line_index.point(start);
+                let old_end_position = line_index.point(end);
+
+                self.text.replace_range(start..end, &change.text);
+
+                let new_end = start + change.text.len();
+
+                let edit = InputEdit {
+                    start_byte: start,
+                    old_end_byte: end,
+                    new_end_byte: new_end,
+                    start_position,
+                    old_end_position,
+                    new_end_position: LineIndex::new(&self.text).point(new_end),
+                };
+
+                if let Some(tree) = &mut self.tree {
+                    tree.edit(&edit);
+                }
+
+                if let Some((tree, _)) = &mut self.flat {
+                    tree.edit(&edit);
+                }
+            }
+            None => {
+                self.text = change.text;
+                self.tree = None;
+                self.flat = None;
+            }
+        }
+    }
+}
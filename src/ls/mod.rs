@@ -1,2 +1,8 @@
 mod backend;
 pub use backend::*;
+
+mod document;
+pub use document::*;
+
+#[cfg(test)]
+mod backend_tests;
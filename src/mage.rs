@@ -1,11 +1,29 @@
-use tree_sitter::{Language, Parser, Tree};
+use std::sync::OnceLock;
+
+use tree_sitter::{Language, Node, Parser, Tree};
 use tree_sitter_mage::LANGUAGE;
 
-use crate::Error;
+use crate::{Error, IntWidth, Linter, NodeKinds};
+
+static NODE_KINDS: OnceLock<NodeKinds> = OnceLock::new();
+static LINTER: OnceLock<Linter> = OnceLock::new();
+
+/// Deepest nesting of sources and parentheses accepted by `parse_text` unless
+/// configured otherwise. The later stages walk the tree recursively.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Deepest tree accepted by `parse_text` however it nests. Each operator of a
+/// chain such as `0d1 + 0d1 + 0d1` adds a level. The recursive stages overflow
+/// a 2 MB stack somewhat above 400 levels in a debug build, so this keeps them
+/// within the stack a spawned thread gets by default while staying above
+/// `DEFAULT_MAX_DEPTH`, which nesting reaches first.
+pub const MAX_TREE_DEPTH: usize = 320;
 
 pub struct Mage {
     pub language: Language,
     pub thread: Thread,
+    pub int_width: IntWidth,
+    pub max_depth: usize,
 }
 
 pub struct Thread {
@@ -19,6 +37,8 @@ impl Mage {
             thread: Thread {
                 parser: Parser::new(),
             },
+            int_width: IntWidth::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
         };
 
         if let Err(error) = mage.thread.parser.set_language(&mage.language) {
@@ -31,11 +51,92 @@ impl Mage {
         }
     }
 
+    /// Node kind ids of the grammar, looked up once per process.
+    pub fn node_kinds(&self) -> &'static NodeKinds {
+        NODE_KINDS.get_or_init(|| NodeKinds::new(&self.language))
+    }
+
+    /// Linter with the default rules, whose queries are compiled once per
+    /// process.
+    pub fn linter(&self) -> Result<&'static Linter, Error> {
+        if let Some(linter) = LINTER.get() {
+            return Ok(linter);
+        }
+
+        let linter = Linter::with_default_rules(&self.language)?;
+
+        Ok(LINTER.get_or_init(|| linter))
+    }
+
+    /// Parses `text`, rejecting trees nested deeper than `max_depth` so that
+    /// the recursive stages after parsing cannot overflow the stack.
     pub fn parse_text(&mut self, text: &str) -> Result<Tree, Error> {
-        if let Some(tree) = self.thread.parser.parse(text, None) {
-            Ok(tree)
-        } else {
-            Err(Error::ParseError("Error: Unable to parse.".to_string()))
+        let Some(tree) = self.thread.parser.parse(text, None) else {
+            return Err(Error::ParseError("Error: Unable to parse.".to_string()));
+        };
+
+        check_depth(&tree, self.node_kinds(), self.max_depth)?;
+
+        Ok(tree)
+    }
+
+    /// Parses `text` reusing `old_tree`, which must already have the edits
+    /// that turned its text into `text` applied with `Tree::edit`.
+    pub fn parse_edited(&mut self, text: &str, old_tree: &Tree) -> Result<Tree, Error> {
+        let Some(tree) = self.thread.parser.parse(text, Some(old_tree)) else {
+            return Err(Error::ParseError("Error: Unable to parse.".to_string()));
+        };
+
+        check_depth(&tree, self.node_kinds(), self.max_depth)?;
+
+        Ok(tree)
+    }
+}
+
+/// Counts how deeply sources and parentheses nest, which is what makes a
+/// program deep to read. Operator chains also deepen the tree, one level per
+/// operator, but are not nesting and only have to stay below
+/// `MAX_TREE_DEPTH`. Walks the tree with a cursor rather than recursively,
+/// since it runs before the depth is known to be safe.
+fn check_depth(tree: &Tree, node_kinds: &NodeKinds, max_depth: usize) -> Result<(), Error> {
+    let nests = |node: Node| {
+        node.kind_id() == node_kinds.source || node.kind_id() == node_kinds.parenthesize
+    };
+
+    let mut cursor = tree.walk();
+    let mut depth = 0;
+
+    loop {
+        if depth > max_depth {
+            return Err(Error::ParseError(format!(
+                "Error: Nesting is deeper than {} levels.",
+                max_depth
+            )));
+        }
+
+        if cursor.depth() as usize > MAX_TREE_DEPTH {
+            return Err(Error::ParseError(format!(
+                "Error: Expression is deeper than {} levels.",
+                MAX_TREE_DEPTH
+            )));
+        }
+
+        if cursor.goto_first_child() {
+            depth += usize::from(nests(cursor.node()));
+            continue;
+        }
+
+        loop {
+            depth -= usize::from(nests(cursor.node()));
+
+            if cursor.goto_next_sibling() {
+                depth += usize::from(nests(cursor.node()));
+                break;
+            }
+
+            if !cursor.goto_parent() {
+                return Ok(());
+            }
         }
     }
 }
@@ -0,0 +1,197 @@
+use std::thread;
+
+use crate::{DEFAULT_MAX_ERRORS, Error, MAX_TREE_DEPTH, Mage, Output, Stage, Timings};
+
+#[test]
+fn test_node_kinds_are_cached() {
+    let first = Mage::new().unwrap();
+    let second = Mage::new().unwrap();
+
+    assert!(std::ptr::eq(first.node_kinds(), second.node_kinds()));
+}
+
+#[test]
+fn test_node_kinds_are_valid() {
+    let mage = Mage::new().unwrap();
+    let node_kinds = mage.node_kinds();
+
+    let ids = [
+        node_kinds.source_file,
+        node_kinds.source,
+        node_kinds.parenthesize,
+        node_kinds.member,
+        node_kinds.call,
+        node_kinds.multiplicative,
+        node_kinds.additive,
+        node_kinds.comparison,
+        node_kinds.logical,
+        node_kinds.assign,
+        node_kinds.binary,
+        node_kinds.octal,
+        node_kinds.decimal,
+        node_kinds.hex,
+        node_kinds.single_quoted,
+        node_kinds.double_quoted,
+        node_kinds.escape,
+        node_kinds.raw,
+        node_kinds.identifier,
+        node_kinds.extract,
+        node_kinds.pipe,
+        node_kinds.multiply,
+        node_kinds.divide,
+        node_kinds.modulo,
+        node_kinds.add,
+        node_kinds.subtract,
+        node_kinds.equal,
+        node_kinds.not_equal,
+        node_kinds.less_than,
+        node_kinds.greater_than,
+        node_kinds.less_equal,
+        node_kinds.greater_equal,
+        node_kinds.and,
+        node_kinds.or,
+        node_kinds.constant,
+        node_kinds.variable,
+    ];
+
+    assert!(ids.iter().all(|id| *id != 0));
+}
+
+#[test]
+fn test_stages_agree() {
+    let mut mage = Mage::new().unwrap();
+
+    let programs = [
+        ("", true),
+        ("0d1 + 0d2", true),
+        ("x : 0d1\nx => f", true),
+        ("{ x : 0d1\nx => f }", true),
+        ("{ { x : 0d1\nx => f } }", true),
+        ("a : b : 0d5\na + b => f", true),
+        ("x :", false),
+        ("0d1 +", false),
+        ("x : 0b12\nx => f", false),
+        ("x : 0x1_0000_0000_0000_0000\nx => f", false),
+        ("a : b = 0d5\na + b => f", false),
+    ];
+
+    for (program, accepted) in programs {
+        match (mage.accepts(program), accepted) {
+            (Ok(()), true) => {}
+            (Err(diagnostics), false) => assert!(
+                diagnostics
+                    .iter()
+                    .all(|diagnostic| !diagnostic.message.contains("rejects what")),
+                "{:?}: {:?}",
+                program,
+                diagnostics
+            ),
+            (result, _) => panic!("{:?}: {:?}", program, result),
+        }
+    }
+}
+
+#[test]
+fn test_nesting_too_deep() {
+    let mut mage = Mage::new().unwrap();
+    let code = format!("{}a{}", "(".repeat(10_000), ")".repeat(10_000));
+
+    assert_eq!(
+        mage.flatten(&code),
+        Err(Error::ParseError(
+            "Error: Nesting is deeper than 256 levels.".to_string()
+        ))
+    );
+    assert!(mage.validate(&code).is_err());
+
+    let code = format!("{}a{}", "{".repeat(100), "}".repeat(100));
+
+    assert!(mage.flatten(&code).is_ok());
+
+    mage.max_depth = 50;
+
+    assert!(mage.flatten(&code).is_err());
+}
+
+#[test]
+fn test_long_operator_chain_is_not_nesting() {
+    let mut mage = Mage::new().unwrap();
+    let code = format!("a : 0d1{}", " + 0d1".repeat(5_000));
+
+    assert_eq!(
+        mage.flatten(&code),
+        Err(Error::ParseError(
+            "Error: Expression is deeper than 320 levels.".to_string()
+        ))
+    );
+}
+
+/// Spawned threads, including the blocking pool of the language server, get
+/// 2 MB of stack by default.
+#[test]
+fn test_max_tree_depth_fits_default_stack() {
+    let chain = format!("a : 0d1{}", " + 0d1".repeat(MAX_TREE_DEPTH - 3));
+    let sources = format!(
+        "{}a{}",
+        "{ ".repeat(MAX_TREE_DEPTH - 2),
+        " }".repeat(MAX_TREE_DEPTH - 2)
+    );
+
+    thread::Builder::new()
+        .stack_size(2 * 1024 * 1024)
+        .spawn(move || {
+            let mut mage = Mage::new().unwrap();
+            mage.max_depth = MAX_TREE_DEPTH;
+
+            for code in [chain, sources] {
+                assert!(mage.flatten(&code).is_ok());
+                assert!(mage.validate(&code).is_ok());
+                assert!(mage.format(&code).is_ok());
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_process_timings() {
+    let mut mage = Mage::new().unwrap();
+    let mut timings = Timings::default();
+
+    mage.process_timed(&Stage::Flatten, "a : 0d1", &mut timings)
+        .unwrap();
+
+    let stages: Vec<&str> = timings.stages.iter().map(|(stage, _)| *stage).collect();
+
+    assert_eq!(stages, vec!["parse", "flatten"]);
+}
+
+#[test]
+fn test_write_output() {
+    let mut mage = Mage::new().unwrap();
+
+    let root = mage.process(&Stage::Flatten, "a : 0d1").unwrap();
+    let mut buffer = Vec::new();
+
+    root.write(&mut buffer, &Output::Json, "a : 0d1", DEFAULT_MAX_ERRORS)
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        format!("{}\n", serde_json::to_string(&root).unwrap())
+    );
+
+    let code = "a : 0d1\nb : a";
+    let diagnostics = mage.process(&Stage::Validate, code).unwrap();
+    let mut buffer = Vec::new();
+
+    diagnostics
+        .write(&mut buffer, &Output::Text, code, DEFAULT_MAX_ERRORS)
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        "2:1: Warning: Unused variable `b`.\n"
+    );
+}
@@ -1,12 +1,23 @@
 use std::{
     fs,
-    io::{self, BufRead},
+    io::{self, BufRead, Write},
+    path::Path,
+    process, thread,
+    time::Duration,
 };
 
 use clap::Parser;
 
-use mage_rs::{Backend, Cli, Command, Mage, Output};
+use mage_rs::{
+    Cli, Command, DEFAULT_DEBOUNCE, Diagnostic, Error, ErrorCode, Input, LineIndex, Mage, Run,
+    Severity, Stage, Timings, Type, Watcher, build_directory, limit_diagnostics,
+};
+
+#[cfg(feature = "lsp")]
+use mage_rs::Backend;
+#[cfg(feature = "lsp")]
 use tokio::runtime::Runtime;
+#[cfg(feature = "lsp")]
 use tower_lsp_server::{LspService, Server};
 
 fn main() {
@@ -16,48 +27,217 @@ fn main() {
         panic!("Mage error {:?}", error);
     });
 
+    mage.int_width = arguments.int_width;
+
     match arguments.command {
         Command::Run(run) => {
-            let process = |mage: &mut Mage, text: &str| match mage.process(&run.stage, text) {
-                Ok(root) => match arguments.output {
-                    Output::Text => println!("{:#?}", &root),
-                    Output::Json => {
-                        println!("{}", serde_json::to_string(&root).unwrap());
+            let process = |mage: &mut Mage, text: &str| {
+                let mut timings = Timings::default();
+                let result = validate(&run, mage, text, &mut timings, arguments.max_errors)
+                    .and_then(|()| mage.process_timed(&run.stage, text, &mut timings));
+
+                if run.time {
+                    eprint!("{}", timings);
+                }
+
+                match result {
+                    Ok(root) => {
+                        root.write(
+                            &mut io::stdout().lock(),
+                            &arguments.output,
+                            text,
+                            arguments.max_errors,
+                        )
+                        .unwrap();
+
+                        match root {
+                            Type::Diagnostics(diagnostics) if run.fails(&diagnostics) => Err(
+                                Error::ValidateError("Error: Validation failed.".to_string()),
+                            ),
+                            _ => Ok(()),
+                        }
+                    }
+                    Err(err) => {
+                        if run.emit_tree
+                            && let Ok(tree) = mage.parse_text(text)
+                        {
+                            eprintln!("{}", tree.root_node().to_sexp());
+                        }
+
+                        Err(err)
                     }
-                },
-                Err(err) => {
-                    panic!("Processing error {:?}", err);
                 }
             };
 
-            match run.path {
-                Some(path) => {
-                    let file = fs::read_to_string(&path).unwrap();
-                    process(&mut mage, file.as_str())
+            match run.input() {
+                Input::File(path) if run.watch => {
+                    let mut watcher = Watcher::new(&path, DEFAULT_DEBOUNCE);
+
+                    loop {
+                        // Clear the screen and move the cursor home.
+                        print!("\x1b[2J\x1b[H");
+
+                        match fs::read_to_string(&path) {
+                            Ok(file) => {
+                                if let Err(err) = process(&mut mage, file.as_str()) {
+                                    eprintln!("{}", err);
+                                }
+                            }
+                            Err(err) => eprintln!("Unable to read {}: {}", path.display(), err),
+                        }
+
+                        io::stdout().flush().unwrap();
+
+                        while !watcher.poll() {
+                            thread::sleep(Duration::from_millis(50));
+                        }
+                    }
+                }
+                Input::File(path) => {
+                    let file = fs::read_to_string(&path).unwrap_or_else(|error| {
+                        eprintln!("{}: {}", path.display(), error);
+                        process::exit(1);
+                    });
+
+                    if let Err(err) = process(&mut mage, file.as_str()) {
+                        eprintln!("{}", err);
+                        process::exit(1);
+                    }
                 }
-                None => {
+                Input::Stdin if run.watch => {
+                    eprintln!("Error: --watch needs a file, not standard input.");
+                    process::exit(1);
+                }
+                Input::Stdin => {
                     let stdin = io::stdin();
+                    let mut failed = false;
 
                     for line in stdin.lock().lines() {
-                        if let Ok(text) = line {
-                            process(&mut mage, text.as_str());
+                        if let Ok(text) = line
+                            && let Err(err) = process(&mut mage, text.as_str())
+                        {
+                            eprintln!("{}", err);
+                            failed = true;
                         }
                     }
+
+                    if failed {
+                        process::exit(1);
+                    }
+                }
+            }
+        }
+        Command::Check(check) => {
+            let text = fs::read_to_string(&check.path).unwrap_or_else(|error| {
+                eprintln!("{}: {}", check.path, error);
+                process::exit(1);
+            });
+
+            let diagnostics = mage
+                .validate(&text)
+                .unwrap_or_else(|error| vec![Diagnostic::from_error(&error, (0, text.len()))]);
+
+            let line_index = LineIndex::new(&text);
+
+            for diagnostic in limit_diagnostics(diagnostics.clone(), arguments.max_errors) {
+                eprintln!("{}:{}", check.path, diagnostic.render(&line_index));
+            }
+
+            if diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.severity == Severity::Error)
+            {
+                process::exit(1);
+            }
+        }
+        Command::Build(build) => {
+            let summary =
+                build_directory(Path::new(&build.path), &build.stage).unwrap_or_else(|error| {
+                    eprintln!("{}", error);
+                    process::exit(1);
+                });
+
+            println!("{}", summary);
+
+            if summary.failed() > 0 {
+                process::exit(1);
+            }
+        }
+        Command::Fmt(fmt) => {
+            let text = fs::read_to_string(&fmt.path).unwrap_or_else(|error| {
+                eprintln!("{}: {}", fmt.path, error);
+                process::exit(1);
+            });
+
+            let formatted = mage.format(&text).unwrap_or_else(|error| {
+                eprintln!("{}: {}", fmt.path, error);
+                process::exit(1);
+            });
+
+            if formatted != text {
+                if fmt.check {
+                    eprintln!("{}: Not formatted.", fmt.path);
+                    process::exit(1);
+                }
+
+                if let Err(error) = fs::write(&fmt.path, formatted) {
+                    eprintln!("{}: {}", fmt.path, error);
+                    process::exit(1);
                 }
             }
         }
+        Command::Explain(explain) => match ErrorCode::from_code(&explain.code) {
+            Some(error_code) => println!("{}", error_code.explain()),
+            None => {
+                eprintln!("Error: Unknown error code `{}`.", explain.code);
+                process::exit(1);
+            }
+        },
         Command::Environment => {
             panic!("Not implemented")
         }
+        #[cfg(feature = "lsp")]
         Command::LanguageServer => {
             let rt = Runtime::new().unwrap();
 
             rt.block_on(async {
                 let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
 
-                let (service, socket) = LspService::new(|client| Backend { client });
+                let (service, socket) =
+                    LspService::new(|client| Backend::with_int_width(client, arguments.int_width));
                 Server::new(stdin, stdout, socket).serve(service).await;
             });
         }
     }
 }
+
+/// Validates `text` ahead of the later stages, printing the diagnostics to
+/// stderr when they stop the run. Skipped for `--no-validate` and for the
+/// validate stage itself, which reports its diagnostics as its output.
+fn validate(
+    run: &Run,
+    mage: &mut Mage,
+    text: &str,
+    timings: &mut Timings,
+    max_errors: usize,
+) -> Result<(), Error> {
+    if run.no_validate || matches!(run.stage, Stage::Validate) {
+        return Ok(());
+    }
+
+    let diagnostics = timings.time("validate", || mage.validate(text))?;
+
+    if !run.fails(&diagnostics) {
+        return Ok(());
+    }
+
+    let line_index = LineIndex::new(text);
+
+    for diagnostic in limit_diagnostics(diagnostics, max_errors) {
+        eprintln!("{}", diagnostic.render(&line_index));
+    }
+
+    Err(Error::ValidateError(
+        "Error: Validation failed.".to_string(),
+    ))
+}
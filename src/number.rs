@@ -0,0 +1,172 @@
+use crate::{Error, IntWidth};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hex,
+}
+
+impl Radix {
+    pub const ALL: [Radix; 4] = [Radix::Binary, Radix::Octal, Radix::Decimal, Radix::Hex];
+
+    pub fn from_prefix(prefix: char) -> Option<Self> {
+        match prefix.to_ascii_lowercase() {
+            'b' => Some(Radix::Binary),
+            'o' => Some(Radix::Octal),
+            'd' => Some(Radix::Decimal),
+            'x' => Some(Radix::Hex),
+            _ => None,
+        }
+    }
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Radix::Binary => "0b",
+            Radix::Octal => "0o",
+            Radix::Decimal => "0d",
+            Radix::Hex => "0x",
+        }
+    }
+
+    pub fn value(self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+            Radix::Hex => 16,
+        }
+    }
+
+    /// Digits may be grouped with single underscores between them, as in
+    /// `1_000`, but may not start or end with one.
+    pub fn accepts(self, digits: &str) -> bool {
+        !digits.starts_with('_')
+            && !digits.ends_with('_')
+            && !digits.contains("__")
+            && digits.chars().any(|digit| digit != '_')
+            && digits
+                .chars()
+                .all(|digit| digit == '_' || digit.is_digit(self.value()))
+    }
+}
+
+/// Splits a literal such as `0xFF` into its radix and digits.
+pub fn split_number(text: &str) -> Option<(Radix, &str)> {
+    let mut chars = text.chars();
+
+    if chars.next() != Some('0') {
+        return None;
+    }
+
+    let radix = Radix::from_prefix(chars.next()?)?;
+
+    Some((radix, &text[2..]))
+}
+
+/// Well-formed literal, including ones with digit separators that the grammar
+/// lexes as identifiers.
+pub fn is_number_literal(text: &str) -> bool {
+    split_number(text).is_some_and(|(radix, digits)| radix.accepts(digits))
+}
+
+impl IntWidth {
+    pub fn bits(self) -> u32 {
+        match self {
+            IntWidth::I32 => 32,
+            IntWidth::I64 => 64,
+        }
+    }
+
+    /// Parses a literal, rejecting values that do not fit in this width.
+    pub fn parse(self, text: &str) -> Result<i64, Error> {
+        self.fit(text, parse_number(text)?)
+    }
+
+    /// Parses a literal under a unary `-`, which reaches one further than the
+    /// literal alone, as in `-0x8000_0000` for 32 bits.
+    pub fn parse_negated(self, text: &str) -> Result<i64, Error> {
+        self.fit(text, parse_negated_number(text)?)
+    }
+
+    fn fit(self, text: &str, value: i64) -> Result<i64, Error> {
+        if self.wrap(value) != value {
+            return Err(out_of_range(text, self));
+        }
+
+        Ok(value)
+    }
+
+    /// Two's complement wrapping of a result to this width.
+    pub fn wrap(self, value: i64) -> i64 {
+        match self {
+            IntWidth::I32 => value as i32 as i64,
+            IntWidth::I64 => value,
+        }
+    }
+}
+
+fn out_of_range(text: &str, int_width: IntWidth) -> Error {
+    Error::NumberOutOfRange {
+        text: text.to_string(),
+        bits: int_width.bits(),
+        span: (0, text.len()),
+    }
+}
+
+/// Value of a literal. Errors span all of `text`, since where it is in the
+/// program is not known here; `Error::with_span` moves them there.
+pub fn parse_number(text: &str) -> Result<i64, Error> {
+    parse_signed(text, false)
+}
+
+/// Value of `-text`. Negating after parsing would reject the literal of
+/// `i64::MIN`, whose magnitude does not fit on its own.
+pub fn parse_negated_number(text: &str) -> Result<i64, Error> {
+    parse_signed(text, true)
+}
+
+fn parse_signed(text: &str, negated: bool) -> Result<i64, Error> {
+    let Some((radix, digits)) = split_number(text).filter(|(radix, digits)| radix.accepts(digits))
+    else {
+        return Err(Error::InvalidNumber {
+            text: text.to_string(),
+            span: (0, text.len()),
+        });
+    };
+
+    let sign = if negated { "-" } else { "" };
+
+    i64::from_str_radix(
+        &format!("{}{}", sign, digits.replace('_', "")),
+        radix.value(),
+    )
+    .map_err(|_| out_of_range(text, IntWidth::I64))
+}
+
+/// Words starting with a digit are lexed as identifiers when they are not a
+/// well-formed literal, e.g. `0b12` or `42`. They are never valid names.
+pub fn is_malformed_number(text: &str) -> bool {
+    let starts_with_digit = text.starts_with(|first: char| first.is_ascii_digit());
+
+    match split_number(text) {
+        Some((radix, digits)) => !radix.accepts(digits),
+        None => starts_with_digit,
+    }
+}
+
+/// Well-formed literals with the same digits as a malformed one, trying each
+/// radix prefix in turn.
+pub fn number_suggestions(text: &str) -> Vec<String> {
+    let (written, digits) = match split_number(text) {
+        Some((radix, digits)) => (Some(radix), digits),
+        None => (None, text),
+    };
+
+    Radix::ALL
+        .iter()
+        .filter(|radix| Some(**radix) != written && radix.accepts(digits))
+        .map(|radix| format!("{}{}", radix.prefix(), digits))
+        .collect()
+}
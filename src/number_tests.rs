@@ -0,0 +1,68 @@
+use crate::{
+    Error, IntWidth, is_malformed_number, is_number_literal, number_suggestions,
+    parse_negated_number, parse_number,
+};
+
+#[test]
+fn test_grouped_literals() {
+    assert_eq!(parse_number("0d1_000"), Ok(1000));
+    assert_eq!(parse_number("0xFF_FF"), Ok(0xFFFF));
+    assert_eq!(parse_number("0b1010_1010"), Ok(0b1010_1010));
+    assert!(is_number_literal("0o7_7"));
+}
+
+#[test]
+fn test_misplaced_separators() {
+    for text in ["0x_FF", "0xFF_", "0d1__000", "0d_"] {
+        assert!(is_malformed_number(text), "{} should be malformed", text);
+        assert_eq!(
+            parse_number(text),
+            Err(Error::InvalidNumber {
+                text: text.to_string(),
+                span: (0, text.len()),
+            })
+        );
+    }
+}
+
+#[test]
+fn test_suggestions() {
+    assert_eq!(number_suggestions("0b12"), vec!["0o12", "0d12", "0x12"]);
+    assert_eq!(number_suggestions("42"), vec!["0o42", "0d42", "0x42"]);
+    assert!(number_suggestions("0xG").is_empty());
+}
+
+#[test]
+fn test_int_width_literals() {
+    assert_eq!(IntWidth::I64.parse("0d2_147_483_648"), Ok(2_147_483_648));
+    assert_eq!(
+        IntWidth::I32.parse("0d2_147_483_648"),
+        Err(Error::NumberOutOfRange {
+            text: "0d2_147_483_648".to_string(),
+            bits: 32,
+            span: (0, 15),
+        })
+    );
+    assert_eq!(IntWidth::I32.parse("0x7FFF_FFFF"), Ok(i32::MAX as i64));
+}
+
+#[test]
+fn test_int_width_wrapping() {
+    assert_eq!(IntWidth::I32.wrap(i32::MAX as i64 + 1), i32::MIN as i64);
+    assert_eq!(IntWidth::I64.wrap(i32::MAX as i64 + 1), i32::MAX as i64 + 1);
+}
+
+#[test]
+fn test_negated_literals() {
+    assert_eq!(parse_negated_number("0d5"), Ok(-5));
+    assert_eq!(
+        parse_negated_number("0d9_223_372_036_854_775_808"),
+        Ok(i64::MIN)
+    );
+    assert!(parse_number("0d9_223_372_036_854_775_808").is_err());
+    assert_eq!(
+        IntWidth::I32.parse_negated("0x8000_0000"),
+        Ok(i32::MIN as i64)
+    );
+    assert!(IntWidth::I32.parse("0x8000_0000").is_err());
+}
@@ -0,0 +1,236 @@
+use std::{
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Mutex,
+};
+
+use tree_sitter::Tree;
+
+use crate::{
+    Diagnostic, Edit, Error, FlatRoot, IntWidth, Mage, Token, flatten_tree, format_edits,
+    format_tree, tokenize_range, validate_syntax_tree, validate_tree,
+};
+
+/// Texts whose trees are kept by `MagePool::parse`.
+const CACHE_CAPACITY: usize = 16;
+
+/// Parsers that can be shared between threads and async tasks.
+///
+/// A tree-sitter parser is `Send` but not `Sync`, so every call checks out a
+/// `Mage` for its duration and returns it afterwards. When all of them are in
+/// use, a new one is created, so callers never wait on each other's parsing;
+/// the lock is only held to take or return an instance.
+#[derive(Default)]
+pub struct MagePool {
+    mages: Mutex<Vec<Mage>>,
+    cache: Mutex<ParseCache>,
+    int_width: IntWidth,
+}
+
+/// How often `MagePool::parse` found a text in its cache.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Trees of the most recently parsed texts, least recently used first, with
+/// their flattened form once it has been asked for. Texts are compared in full
+/// after their hashes, so a collision is only a miss.
+#[derive(Default)]
+struct ParseCache {
+    entries: Vec<CacheEntry>,
+    stats: CacheStats,
+}
+
+struct CacheEntry {
+    hash: u64,
+    text: String,
+    tree: Tree,
+    flat: Option<Result<FlatRoot, Error>>,
+}
+
+impl ParseCache {
+    /// Entry of `text`, moved to the most recently used end.
+    fn get(&mut self, hash: u64, text: &str) -> Option<&mut CacheEntry> {
+        let Some(position) = self
+            .entries
+            .iter()
+            .position(|entry| entry.hash == hash && entry.text == text)
+        else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        self.stats.hits += 1;
+
+        let entry = self.entries.remove(position);
+        self.entries.push(entry);
+
+        self.entries.last_mut()
+    }
+
+    fn insert(&mut self, hash: u64, text: &str, tree: Tree) {
+        if self.entries.len() == CACHE_CAPACITY {
+            self.entries.remove(0);
+        }
+
+        self.entries.push(CacheEntry {
+            hash,
+            text: text.to_string(),
+            tree,
+            flat: None,
+        });
+    }
+
+    /// Remembers the flattened form of `text` if its tree is still cached.
+    fn insert_flat(&mut self, hash: u64, text: &str, flat: &Result<FlatRoot, Error>) {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.hash == hash && entry.text == text)
+        {
+            entry.flat = Some(flat.clone());
+        }
+    }
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl fmt::Debug for MagePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MagePool")
+            .field("idle", &self.mages.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl MagePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pool whose instances check numbers at `int_width`.
+    pub fn with_int_width(int_width: IntWidth) -> Self {
+        Self {
+            int_width,
+            ..Self::default()
+        }
+    }
+
+    pub fn with<T>(&self, f: impl FnOnce(&mut Mage) -> Result<T, Error>) -> Result<T, Error> {
+        let idle = self.mages.lock().unwrap().pop();
+
+        let mut mage = match idle {
+            Some(mage) => mage,
+            None => {
+                let mut mage = Mage::new()?;
+                mage.int_width = self.int_width;
+                mage
+            }
+        };
+
+        let result = f(&mut mage);
+
+        self.mages.lock().unwrap().push(mage);
+
+        result
+    }
+
+    /// Parses `text`, reusing the tree of an identical text parsed recently.
+    /// Edited texts differ from what was cached, so they are parsed afresh.
+    pub fn parse(&self, text: &str) -> Result<Tree, Error> {
+        Ok(self.cached(hash_text(text), text)?.0)
+    }
+
+    /// Tree of `text` and its flattened form if that is cached too.
+    fn cached(
+        &self,
+        hash: u64,
+        text: &str,
+    ) -> Result<(Tree, Option<Result<FlatRoot, Error>>), Error> {
+        if let Some(entry) = self.cache.lock().unwrap().get(hash, text) {
+            return Ok((entry.tree.clone(), entry.flat.clone()));
+        }
+
+        let tree = self.with(|mage| mage.parse_text(text))?;
+
+        self.cache.lock().unwrap().insert(hash, text, tree.clone());
+
+        Ok((tree, None))
+    }
+
+    /// Runs `f` with a checked out `Mage` and the tree of `text`, which comes
+    /// from the cache when it can.
+    pub fn with_tree<T>(
+        &self,
+        text: &str,
+        f: impl FnOnce(&mut Mage, &Tree) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let tree = self.parse(text)?;
+
+        self.with(|mage| f(mage, &tree))
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().unwrap().stats
+    }
+
+    /// Flattens `text`, reusing the result for an identical text flattened
+    /// recently.
+    pub fn flatten(&self, text: &str) -> Result<FlatRoot, Error> {
+        let hash = hash_text(text);
+
+        let tree = match self.cached(hash, text)? {
+            (_, Some(flat)) => return flat,
+            (tree, None) => tree,
+        };
+
+        let flat =
+            self.with(|mage| Ok(flatten_tree(mage.node_kinds(), &tree, text, mage.int_width)))?;
+
+        self.cache.lock().unwrap().insert_flat(hash, text, &flat);
+
+        flat
+    }
+
+    pub fn validate(&self, text: &str) -> Result<Vec<Diagnostic>, Error> {
+        self.with_tree(text, |mage, tree| {
+            Ok(validate_tree(
+                mage.node_kinds(),
+                mage.linter()?,
+                tree,
+                text,
+                mage.int_width,
+            ))
+        })
+    }
+
+    pub fn validate_syntax(&self, text: &str) -> Result<Vec<Diagnostic>, Error> {
+        self.with_tree(text, |mage, tree| {
+            Ok(validate_syntax_tree(mage.node_kinds(), tree, text))
+        })
+    }
+
+    pub fn format(&self, text: &str) -> Result<String, Error> {
+        self.with_tree(text, |mage, tree| {
+            format_tree(mage.node_kinds(), tree, text)
+        })
+    }
+
+    pub fn format_edits(&self, text: &str) -> Result<Vec<Edit>, Error> {
+        let formatted = self.format(text)?;
+
+        Ok(format_edits(text, &formatted))
+    }
+
+    pub fn tokens_range(&self, text: &str, span: (usize, usize)) -> Result<Vec<Token>, Error> {
+        self.with_tree(text, |mage, tree| {
+            Ok(tokenize_range(mage.node_kinds(), tree, text, span))
+        })
+    }
+}
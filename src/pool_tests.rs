@@ -0,0 +1,66 @@
+use std::thread;
+
+use crate::{CacheStats, Mage, MagePool};
+
+#[test]
+fn test_concurrent_flatten() {
+    let pool = MagePool::new();
+    let code = "a : 0d1 + b * 0d2\nb = a";
+
+    let expected = Mage::new().unwrap().flatten(code).unwrap();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| scope.spawn(|| (0..16).map(|_| pool.flatten(code)).collect::<Vec<_>>()))
+            .collect();
+
+        for handle in handles {
+            for root in handle.join().unwrap() {
+                assert_eq!(root.as_ref(), Ok(&expected));
+            }
+        }
+    });
+}
+
+#[test]
+fn test_parsers_are_reused() {
+    let pool = MagePool::new();
+
+    pool.parse("a : 0d1").unwrap();
+    pool.validate("a : 0d1").unwrap();
+
+    assert_eq!(format!("{:?}", pool), "MagePool { idle: 1 }");
+}
+
+#[test]
+fn test_parse_cache() {
+    let pool = MagePool::new();
+
+    let first = pool.parse("a : 0d1").unwrap();
+    let second = pool.parse("a : 0d1").unwrap();
+
+    assert_eq!(first.root_node().to_sexp(), second.root_node().to_sexp());
+    assert_eq!(pool.cache_stats(), CacheStats { hits: 1, misses: 1 });
+
+    pool.parse("a : 0d2").unwrap();
+    pool.flatten("a : 0d1").unwrap();
+
+    assert_eq!(pool.cache_stats(), CacheStats { hits: 2, misses: 2 });
+}
+
+#[test]
+fn test_stages_share_cache() {
+    let pool = MagePool::new();
+    let code = "a : 0d1\na => print";
+
+    let flat = pool.flatten(code).unwrap();
+
+    assert_eq!(pool.flatten(code), Ok(flat));
+
+    pool.validate(code).unwrap();
+    pool.validate_syntax(code).unwrap();
+    pool.format_edits(code).unwrap();
+    pool.tokens_range(code, (0, code.len())).unwrap();
+
+    assert_eq!(pool.cache_stats(), CacheStats { hits: 5, misses: 1 });
+}
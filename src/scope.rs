@@ -0,0 +1,147 @@
+use tree_sitter::{Node, Tree};
+
+use crate::NodeKinds;
+
+/// Every identifier in `tree` with the same name as `identifier` that refers
+/// to the same binding, the definition included, in source order. Names that
+/// are never bound match wherever they are unbound.
+pub(crate) fn uses_of<'tree>(
+    node_kinds: &NodeKinds,
+    tree: &'tree Tree,
+    code: &str,
+    identifier: Node<'tree>,
+) -> Vec<Node<'tree>> {
+    let name = &code[identifier.byte_range()];
+    let definition = definition_of(node_kinds, code, identifier);
+
+    let mut identifiers = Vec::new();
+    collect_identifiers(node_kinds, tree.root_node(), &mut identifiers);
+
+    identifiers
+        .into_iter()
+        .filter(|node| {
+            &code[node.byte_range()] == name && definition_of(node_kinds, code, *node) == definition
+        })
+        .collect()
+}
+
+fn collect_identifiers<'tree>(
+    node_kinds: &NodeKinds,
+    node: Node<'tree>,
+    identifiers: &mut Vec<Node<'tree>>,
+) {
+    if node.kind_id() == node_kinds.identifier {
+        identifiers.push(node);
+    }
+
+    for child in node.named_children(&mut node.walk()) {
+        collect_identifiers(node_kinds, child, identifiers);
+    }
+}
+
+/// Name bound by the definition `identifier` refers to. Plain names and call
+/// targets, such as `f` in `x => f`, resolve to the nearest enclosing source
+/// binding them. The name after `.` resolves inside the source that the left
+/// operand is bound to, so `b` in `a.b` finds the `b` defined in `a`.
+pub(crate) fn definition_of<'tree>(
+    node_kinds: &NodeKinds,
+    code: &str,
+    identifier: Node<'tree>,
+) -> Option<Node<'tree>> {
+    lookup(node_kinds, code, identifier, &code[identifier.byte_range()])
+}
+
+/// Definition that `name` would refer to if it were written in place of
+/// `identifier`.
+pub(crate) fn lookup<'tree>(
+    node_kinds: &NodeKinds,
+    code: &str,
+    identifier: Node<'tree>,
+    name: &str,
+) -> Option<Node<'tree>> {
+    if let Some(member) = identifier.parent()
+        && member.kind_id() == node_kinds.member
+        && member.named_child(2) == Some(identifier)
+    {
+        let source = bound_source(node_kinds, code, member.named_child(0)?)?;
+
+        return binding_in(node_kinds, source, code, name);
+    }
+
+    let mut scope = identifier.parent();
+
+    while let Some(node) = scope {
+        if (node.kind_id() == node_kinds.source || node.parent().is_none())
+            && let Some(definition) = binding_in(node_kinds, node, code, name)
+        {
+            return Some(definition);
+        }
+
+        scope = node.parent();
+    }
+
+    None
+}
+
+/// Source literal that `node` is, or is bound to through a name or a member.
+fn bound_source<'tree>(
+    node_kinds: &NodeKinds,
+    code: &str,
+    node: Node<'tree>,
+) -> Option<Node<'tree>> {
+    let kind = node.kind_id();
+
+    if kind == node_kinds.source {
+        return Some(node);
+    }
+
+    if kind == node_kinds.parenthesize {
+        return bound_source(node_kinds, code, node.named_child(0)?);
+    }
+
+    let identifier = if kind == node_kinds.member {
+        node.named_child(2)?
+    } else {
+        node
+    };
+
+    if identifier.kind_id() != node_kinds.identifier {
+        return None;
+    }
+
+    // In `a : b : { ... }` both names are bound to the source.
+    let mut value = definition_of(node_kinds, code, identifier)?
+        .parent()?
+        .named_child(2)?;
+
+    while value.kind_id() == node_kinds.assign {
+        value = value.named_child(2)?;
+    }
+
+    (value.kind_id() == node_kinds.source).then_some(value)
+}
+
+/// First target named `name` bound directly in `source`, outside any nested
+/// source.
+fn binding_in<'tree>(
+    node_kinds: &NodeKinds,
+    source: Node<'tree>,
+    code: &str,
+    name: &str,
+) -> Option<Node<'tree>> {
+    source.named_children(&mut source.walk()).find_map(|child| {
+        if child.kind_id() == node_kinds.source {
+            return None;
+        }
+
+        if child.kind_id() == node_kinds.assign
+            && let Some(target) = child.named_child(0)
+            && target.kind_id() == node_kinds.identifier
+            && &code[target.byte_range()] == name
+        {
+            return Some(target);
+        }
+
+        binding_in(node_kinds, child, code, name)
+    })
+}
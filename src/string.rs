@@ -0,0 +1,106 @@
+use crate::Error;
+
+/// Value of a quoted literal such as `"a\n"`, with its quotes removed and its
+/// escapes decoded as the grammar spells them: single characters (`\n`,
+/// `\t`, `\'`, ...), octal `\0` to `\777`, `\xHH`, `\uHHHH`, `\u{H...}`, and
+/// a backslash before a line break, which continues the line.
+pub fn decode_string(text: &str) -> Result<String, Error> {
+    decode_string_at(text, 0)
+}
+
+/// Like `decode_string` for a literal found at byte `start` of a program, so
+/// that an invalid escape is reported where it is in the program.
+pub fn decode_string_at(text: &str, start: usize) -> Result<String, Error> {
+    let body = text
+        .get(1..text.len().saturating_sub(1))
+        .filter(|_| text.len() >= 2)
+        .ok_or_else(|| Error::ParseError(format!("Error: Invalid string literal {}.", text)))?;
+
+    let mut decoded = String::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((index, character)) = chars.next() {
+        if character != '\\' {
+            decoded.push(character);
+            continue;
+        }
+
+        let invalid = |end: usize| Error::InvalidEscape {
+            escape: body[index..end].to_string(),
+            span: (start + 1 + index, start + 1 + end),
+        };
+
+        let Some((_, escaped)) = chars.next() else {
+            return Err(invalid(body.len()));
+        };
+
+        let value = match escaped {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            'b' => Some('\u{8}'),
+            'f' => Some('\u{c}'),
+            'v' => Some('\u{b}'),
+            '\r' => {
+                chars.next_if(|(_, next)| matches!(next, '\n' | '\u{2028}' | '\u{2029}'));
+                continue;
+            }
+            '\n' | '\u{2028}' | '\u{2029}' => continue,
+            '0'..='7' => {
+                let mut digits = escaped.to_string();
+
+                while digits.len() < 3
+                    && let Some((_, digit)) = chars.next_if(|(_, digit)| digit.is_digit(8))
+                {
+                    digits.push(digit);
+                }
+
+                code_point(&digits, 8)
+            }
+            'x' => {
+                let digits: String = (0..2)
+                    .filter_map(|_| chars.next().map(|(_, digit)| digit))
+                    .collect();
+
+                code_point(&digits, 16).filter(|_| digits.len() == 2)
+            }
+            'u' if chars.next_if(|(_, next)| *next == '{').is_some() => {
+                let mut digits = String::new();
+
+                while let Some((_, digit)) = chars.next_if(|(_, digit)| *digit != '}') {
+                    digits.push(digit);
+                }
+
+                // Without the closing brace the escape runs to the end of the
+                // string and is not one.
+                chars
+                    .next_if(|(_, next)| *next == '}')
+                    .and_then(|_| code_point(&digits, 16))
+            }
+            'u' => {
+                let digits: String = (0..4)
+                    .filter_map(|_| chars.next().map(|(_, digit)| digit))
+                    .collect();
+
+                code_point(&digits, 16).filter(|_| digits.len() == 4)
+            }
+            other => Some(other),
+        };
+
+        let Some(value) = value else {
+            return Err(invalid(chars.peek().map_or(body.len(), |(end, _)| *end)));
+        };
+
+        decoded.push(value);
+    }
+
+    Ok(decoded)
+}
+
+fn code_point(digits: &str, radix: u32) -> Option<char> {
+    if digits.is_empty() || !digits.chars().all(|digit| digit.is_digit(radix)) {
+        return None;
+    }
+
+    char::from_u32(u32::from_str_radix(digits, radix).ok()?)
+}
@@ -0,0 +1,41 @@
+use crate::{Error, decode_string, decode_string_at};
+
+#[test]
+fn test_decode_escapes() {
+    assert_eq!(decode_string(r#""a\nb""#), Ok("a\nb".to_string()));
+    assert_eq!(decode_string(r"'\t\'\\'"), Ok("\t'\\".to_string()));
+    assert_eq!(decode_string(r#""\x41B\u{43}""#), Ok("ABC".to_string()));
+    assert_eq!(decode_string(r#""\101\0""#), Ok("A\0".to_string()));
+    assert_eq!(decode_string(r#""\q""#), Ok("q".to_string()));
+    assert_eq!(decode_string("\"a\\\nb\""), Ok("ab".to_string()));
+    assert_eq!(decode_string("''"), Ok(String::new()));
+}
+
+#[test]
+fn test_invalid_escapes() {
+    for (text, escape, span) in [
+        (r#""\u{110000}""#, r"\u{110000}", (1, 11)),
+        (r#""\uD800""#, r"\uD800", (1, 7)),
+        (r#""\x4""#, r"\x4", (1, 4)),
+        (r#""a\u{41""#, r"\u{41", (2, 7)),
+        ("\"\\\"", "\\", (1, 2)),
+    ] {
+        assert_eq!(
+            decode_string(text),
+            Err(Error::InvalidEscape {
+                escape: escape.to_string(),
+                span,
+            }),
+            "{}",
+            text
+        );
+    }
+
+    assert_eq!(
+        decode_string_at(r#""\uD800""#, 4),
+        Err(Error::InvalidEscape {
+            escape: r"\uD800".to_string(),
+            span: (5, 11),
+        })
+    );
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+use crate::{Error, Mage, NodeKinds, is_number_literal};
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum TokenKind {
+    Number,
+    String,
+    Operator,
+    Identifier,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: (usize, usize),
+}
+
+impl Mage {
+    pub fn tokens(&mut self, code: &str) -> Result<impl Iterator<Item = Token>, Error> {
+        let tree = self.parse_text(code)?;
+
+        Ok(tokenize_tree(self.node_kinds(), &tree, code).into_iter())
+    }
+
+    pub fn tokens_range(&mut self, code: &str, span: (usize, usize)) -> Result<Vec<Token>, Error> {
+        let tree = self.parse_text(code)?;
+
+        Ok(tokenize_range(self.node_kinds(), &tree, code, span))
+    }
+}
+
+/// Classifies the leaves of `tree` in source order. Strings are reported as a
+/// single token including their quotes and escapes.
+pub fn tokenize_tree(node_kinds: &NodeKinds, tree: &Tree, code: &str) -> Vec<Token> {
+    tokenize_range(node_kinds, tree, code, (0, code.len()))
+}
+
+/// Tokens overlapping `span`. Subtrees outside of it are skipped rather than
+/// walked, so the cost follows the size of the span, not of the file.
+pub fn tokenize_range(
+    node_kinds: &NodeKinds,
+    tree: &Tree,
+    code: &str,
+    span: (usize, usize),
+) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut cursor = tree.walk();
+
+    loop {
+        let node = cursor.node();
+        let outside = node.end_byte() <= span.0 || node.start_byte() >= span.1;
+        let kind = if outside {
+            None
+        } else {
+            classify(node_kinds, node, code)
+        };
+
+        if let Some(kind) = kind {
+            tokens.push(Token {
+                kind,
+                span: (node.start_byte(), node.end_byte()),
+            });
+        }
+
+        if kind.is_none() && !outside && cursor.goto_first_child() {
+            continue;
+        }
+
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return tokens;
+            }
+        }
+    }
+}
+
+fn classify(node_kinds: &NodeKinds, node: Node, code: &str) -> Option<TokenKind> {
+    let kind = node.kind_id();
+
+    if node.is_missing() {
+        None
+    } else if [
+        node_kinds.binary,
+        node_kinds.octal,
+        node_kinds.decimal,
+        node_kinds.hex,
+    ]
+    .contains(&kind)
+    {
+        Some(TokenKind::Number)
+    } else if kind == node_kinds.single_quoted || kind == node_kinds.double_quoted {
+        Some(TokenKind::String)
+    } else if kind == node_kinds.identifier && is_number_literal(&code[node.byte_range()]) {
+        Some(TokenKind::Number)
+    } else if kind == node_kinds.identifier {
+        Some(TokenKind::Identifier)
+    } else if [
+        node_kinds.extract,
+        node_kinds.pipe,
+        node_kinds.multiply,
+        node_kinds.divide,
+        node_kinds.modulo,
+        node_kinds.add,
+        node_kinds.subtract,
+        node_kinds.equal,
+        node_kinds.not_equal,
+        node_kinds.less_than,
+        node_kinds.greater_than,
+        node_kinds.less_equal,
+        node_kinds.greater_equal,
+        node_kinds.and,
+        node_kinds.or,
+        node_kinds.constant,
+        node_kinds.variable,
+    ]
+    .contains(&kind)
+    {
+        Some(TokenKind::Operator)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{Mage, Token, TokenKind};
+
+#[test]
+fn test_token_kinds() {
+    let mut mage = Mage::new().unwrap();
+
+    let kinds: Vec<TokenKind> = mage
+        .tokens("x : (0d1 + y)")
+        .unwrap()
+        .map(|token| token.kind)
+        .collect();
+
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Identifier,
+            TokenKind::Operator,
+            TokenKind::Number,
+            TokenKind::Operator,
+            TokenKind::Identifier,
+        ]
+    );
+}
+
+#[test]
+fn test_string_token_spans() {
+    let mut mage = Mage::new().unwrap();
+
+    let tokens: Vec<Token> = mage.tokens("s : 'a\\n' => f").unwrap().collect();
+
+    assert_eq!(
+        tokens[2],
+        Token {
+            kind: TokenKind::String,
+            span: (4, 9),
+        }
+    );
+    assert_eq!(tokens.len(), 5);
+}
+
+#[test]
+fn test_tokens_range_skips_outside() {
+    let mut mage = Mage::new().unwrap();
+
+    let spans: Vec<(usize, usize)> = mage
+        .tokens_range("a : 0d1\nb : 0d2\nc : 0d3", (8, 15))
+        .unwrap()
+        .iter()
+        .map(|token| token.span)
+        .collect();
+
+    assert_eq!(spans, vec![(8, 9), (10, 11), (12, 15)]);
+}
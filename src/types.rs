@@ -0,0 +1,223 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use tree_sitter::Node;
+
+use crate::{Error, NodeKinds, Scope, is_number_literal};
+
+/// Type of a value as far as it is known before running the program.
+/// Variables, calls and members have no known type.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ValueType {
+    Number,
+    String,
+    Source,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Number => write!(f, "number"),
+            ValueType::String => write!(f, "string"),
+            ValueType::Source => write!(f, "source"),
+        }
+    }
+}
+
+/// Finds the first operator applied to values of the wrong type, such as
+/// `"a" * 0d2`, `"a" + 0d1` or `x => 0d1`. Types come from literals and
+/// constants; anything unknown is assumed to fit. Sources whose span is in
+/// `skip` are not checked.
+pub(crate) fn type_error(
+    node_kinds: &NodeKinds,
+    root: Node,
+    code: &str,
+    skip: &HashSet<(usize, usize)>,
+) -> Option<Error> {
+    let scope = Scope::new(node_kinds, root, code, None);
+
+    check_source(
+        node_kinds,
+        root,
+        code,
+        &scope,
+        skip,
+        &mut Vec::new(),
+        &mut HashMap::new(),
+    )
+    .err()
+}
+
+fn check_source<'a>(
+    node_kinds: &NodeKinds,
+    source: Node,
+    code: &'a str,
+    scope: &Scope,
+    skip: &HashSet<(usize, usize)>,
+    visiting: &mut Vec<&'a str>,
+    known: &mut HashMap<usize, Option<ValueType>>,
+) -> Result<(), Error> {
+    for child in source.named_children(&mut source.walk()) {
+        infer(node_kinds, child, code, scope, skip, visiting, known)?;
+    }
+
+    Ok(())
+}
+
+/// `visiting` holds the constants being inferred, so that circular
+/// definitions have no type instead of recursing forever. That includes
+/// sources referring to the constant they are bound to, as in `f : { => f }`.
+/// `known` holds the type of each value bound to a constant by node id, so
+/// that every definition is inferred once however often it is used.
+fn infer<'a>(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &'a str,
+    scope: &Scope,
+    skip: &HashSet<(usize, usize)>,
+    visiting: &mut Vec<&'a str>,
+    known: &mut HashMap<usize, Option<ValueType>>,
+) -> Result<Option<ValueType>, Error> {
+    let kind = node.kind_id();
+    let text = &code[node.byte_range()];
+
+    if [
+        node_kinds.binary,
+        node_kinds.octal,
+        node_kinds.decimal,
+        node_kinds.hex,
+    ]
+    .contains(&kind)
+        || (kind == node_kinds.identifier && is_number_literal(text))
+    {
+        return Ok(Some(ValueType::Number));
+    }
+
+    if kind == node_kinds.single_quoted || kind == node_kinds.double_quoted {
+        return Ok(Some(ValueType::String));
+    }
+
+    if kind == node_kinds.identifier {
+        let Some((value, defined)) = scope.lookup(text).filter(|_| !visiting.contains(&text))
+        else {
+            return Ok(None);
+        };
+
+        if let Some(value_type) = known.get(&value.id()) {
+            return Ok(*value_type);
+        }
+
+        visiting.push(text);
+        let value_type = infer(node_kinds, value, code, defined, skip, visiting, known);
+        visiting.pop();
+
+        if let Ok(value_type) = value_type {
+            known.insert(value.id(), value_type);
+        }
+
+        return value_type;
+    }
+
+    if kind == node_kinds.source {
+        if !skip.contains(&(node.start_byte(), node.end_byte())) {
+            let nested = Scope::new(node_kinds, node, code, Some(scope));
+            check_source(node_kinds, node, code, &nested, skip, visiting, known)?;
+        }
+
+        return Ok(Some(ValueType::Source));
+    }
+
+    if kind == node_kinds.parenthesize {
+        return match node.named_child(0) {
+            Some(child) => infer(node_kinds, child, code, scope, skip, visiting, known),
+            None => Ok(None),
+        };
+    }
+
+    let children: Vec<Node> = node.named_children(&mut node.walk()).collect();
+
+    let (one, operator, two) = match children.as_slice() {
+        [one, operator, two] => (Some(*one), *operator, *two),
+        [operator, two] => (None, *operator, *two),
+        _ => return Ok(None),
+    };
+
+    // The type of `a : b : 0d5` is the type bound to `b`.
+    if kind == node_kinds.assign {
+        return infer(node_kinds, two, code, scope, skip, visiting, known);
+    }
+
+    let mut one_type = match one {
+        Some(one) => infer(node_kinds, one, code, scope, skip, visiting, known)?,
+        None => None,
+    };
+
+    // In `a < b < c` the second comparison is between `b` and `c`.
+    if kind == node_kinds.comparison
+        && let Some(one) = one
+        && one.kind_id() == node_kinds.comparison
+        && let Some(middle) = one.named_child(2)
+    {
+        one_type = infer(node_kinds, middle, code, scope, skip, visiting, known)?;
+    }
+
+    if kind == node_kinds.member {
+        return Ok(None);
+    }
+
+    let two_type = infer(node_kinds, two, code, scope, skip, visiting, known)?;
+    let span = (node.start_byte(), node.end_byte());
+
+    if kind == node_kinds.call {
+        if let Some(callee @ (ValueType::Number | ValueType::String)) = two_type {
+            return Err(Error::TypeError {
+                message: format!("Error: Cannot call a {}.", callee),
+                span,
+            });
+        }
+
+        return Ok(None);
+    }
+
+    let operator_kind = operator.kind_id();
+    let symbol = &code[operator.byte_range()];
+
+    let accepts = |value_type: ValueType| match value_type {
+        ValueType::Number => true,
+        ValueType::String => {
+            (operator_kind == node_kinds.add && one.is_some()) || kind == node_kinds.comparison
+        }
+        ValueType::Source => {
+            operator_kind == node_kinds.equal || operator_kind == node_kinds.not_equal
+        }
+    };
+
+    for value_type in [one_type, two_type].into_iter().flatten() {
+        if !accepts(value_type) {
+            return Err(Error::TypeError {
+                message: format!("Error: Cannot apply `{}` to a {}.", symbol, value_type),
+                span,
+            });
+        }
+    }
+
+    if let (Some(one_type), Some(two_type)) = (one_type, two_type)
+        && one_type != two_type
+    {
+        return Err(Error::TypeError {
+            message: format!(
+                "Error: Cannot apply `{}` to a {} and a {}.",
+                symbol, one_type, two_type
+            ),
+            span,
+        });
+    }
+
+    if kind == node_kinds.comparison || kind == node_kinds.logical {
+        return Ok(Some(ValueType::Number));
+    }
+
+    Ok(one_type.or(two_type))
+}
@@ -0,0 +1,361 @@
+use std::collections::{HashMap, HashSet};
+
+use tree_sitter::{Node, Tree};
+
+use crate::{
+    Diagnostic, Error, ErrorCode, FlatRoot, IntWidth, Linter, NodeKinds, decode_string_at,
+    definition_of, definition_without_expression, flatten_tree, is_malformed_number,
+    is_mixed_definition_chain, is_negated, is_number_literal, number_suggestions,
+};
+
+/// Cheap check that only reports syntax errors, suitable for every keystroke.
+pub fn validate_syntax_tree(node_kinds: &NodeKinds, tree: &Tree, code: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    validate_syntax(node_kinds, tree.root_node(), code, &mut diagnostics);
+
+    diagnostics
+}
+
+/// Full check: syntax, number literals, definition chains, everything
+/// flatten rejects, unused names and the lints of `linter`.
+pub fn validate_tree(
+    node_kinds: &NodeKinds,
+    linter: &Linter,
+    tree: &Tree,
+    code: &str,
+    int_width: IntWidth,
+) -> Vec<Diagnostic> {
+    validate_tree_with(node_kinds, linter, tree, code, int_width, || {
+        flatten_tree(node_kinds, tree, code, int_width)
+    })
+    .0
+}
+
+/// `validate_tree` with `flatten` producing the flattened form of `tree`, as
+/// `flatten_incremental` does from an earlier root. Also returns that form,
+/// unless the checks before flattening failed or flattening did.
+pub fn validate_tree_with(
+    node_kinds: &NodeKinds,
+    linter: &Linter,
+    tree: &Tree,
+    code: &str,
+    int_width: IntWidth,
+    flatten: impl FnOnce() -> Result<FlatRoot, Error>,
+) -> (Vec<Diagnostic>, Option<FlatRoot>) {
+    let mut diagnostics = validate_syntax_tree(node_kinds, tree, code);
+
+    if !diagnostics.is_empty() {
+        return (diagnostics, None);
+    }
+
+    // Flatten rejects these too, but stops at the first and offers no fixes.
+    validate_numbers(
+        node_kinds,
+        tree.root_node(),
+        code,
+        int_width,
+        &mut diagnostics,
+    );
+    validate_strings(node_kinds, tree.root_node(), code, &mut diagnostics);
+    validate_chains(node_kinds, tree.root_node(), &mut diagnostics);
+
+    if !diagnostics.is_empty() {
+        return (diagnostics, None);
+    }
+
+    let root = match flatten() {
+        Ok(root) => root,
+        Err(error) => {
+            diagnostics.push(Diagnostic::from_error(&error, (0, code.len())));
+            return (diagnostics, None);
+        }
+    };
+
+    validate_unused(node_kinds, tree.root_node(), code, &mut diagnostics);
+    diagnostics.extend(linter.lint(node_kinds, tree, code));
+
+    (diagnostics, Some(root))
+}
+
+fn validate_syntax(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let span = (node.start_byte(), node.end_byte());
+
+    if let Some(error) = definition_without_expression(node_kinds, node, code) {
+        diagnostics.push(Diagnostic::from_error(&error, span));
+    } else if node.is_error() {
+        let text = &code[span.0..span.1];
+
+        diagnostics.push(
+            Diagnostic::error(format!("Error: Unexpected `{}`.", text.trim()), span)
+                .with_code(ErrorCode::UnexpectedSyntax),
+        );
+    } else if node.is_missing() {
+        diagnostics.push(
+            Diagnostic::error(format!("Error: Missing {}.", node.kind()), span)
+                .with_code(ErrorCode::MissingSyntax),
+        );
+    } else if node.has_error() {
+        for child in node.children(&mut node.walk()) {
+            validate_syntax(node_kinds, child, code, diagnostics);
+        }
+    }
+}
+
+fn validate_numbers(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+    int_width: IntWidth,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let kind = node.kind_id();
+    let text = &code[node.byte_range()];
+
+    let is_number = [
+        node_kinds.binary,
+        node_kinds.octal,
+        node_kinds.decimal,
+        node_kinds.hex,
+    ]
+    .contains(&kind)
+        || (kind == node_kinds.identifier && is_number_literal(text));
+
+    if is_number {
+        let value = if is_negated(node_kinds, node) {
+            int_width.parse_negated(text)
+        } else {
+            int_width.parse(text)
+        };
+
+        if let Err(error) = value {
+            let span = (node.start_byte(), node.end_byte());
+
+            diagnostics.push(Diagnostic::from_error(&error.with_span(span), span));
+        }
+
+        return;
+    }
+
+    if kind == node_kinds.identifier {
+        if is_malformed_number(text) {
+            let span = (node.start_byte(), node.end_byte());
+
+            let diagnostic = number_suggestions(text).into_iter().fold(
+                Diagnostic::error(format!("Error: Invalid number literal `{}`.", text), span)
+                    .with_code(ErrorCode::InvalidNumber),
+                |diagnostic, suggestion| {
+                    diagnostic.with_fix(format!("Replace with `{}`", suggestion), span, suggestion)
+                },
+            );
+
+            diagnostics.push(diagnostic);
+        }
+
+        return;
+    }
+
+    for child in node.named_children(&mut node.walk()) {
+        validate_numbers(node_kinds, child, code, int_width, diagnostics);
+    }
+}
+
+fn validate_strings(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let kind = node.kind_id();
+
+    if kind == node_kinds.single_quoted || kind == node_kinds.double_quoted {
+        let span = (node.start_byte(), node.end_byte());
+
+        if let Err(error) = decode_string_at(&code[span.0..span.1], span.0) {
+            diagnostics.push(Diagnostic::from_error(&error, span));
+        }
+
+        return;
+    }
+
+    for child in node.named_children(&mut node.walk()) {
+        validate_strings(node_kinds, child, code, diagnostics);
+    }
+}
+
+fn validate_chains(node_kinds: &NodeKinds, node: Node, diagnostics: &mut Vec<Diagnostic>) {
+    if is_mixed_definition_chain(node_kinds, node) {
+        diagnostics.push(
+            Diagnostic::error(
+                "Error: Definition chain mixes constant `:` and variable `=` bindings.".to_string(),
+                (node.start_byte(), node.end_byte()),
+            )
+            .with_code(ErrorCode::MixedDefinitionChain),
+        );
+    }
+
+    for child in node.named_children(&mut node.walk()) {
+        validate_chains(node_kinds, child, diagnostics);
+    }
+}
+
+/// Warns about names bound in `root` or a source nested in it that no
+/// identifier refers to. Each use is resolved to its binding the way
+/// `definition_of` does, so a shadowed name or a member of another source
+/// does not count as a use of an outer binding.
+fn validate_unused(
+    node_kinds: &NodeKinds,
+    root: Node,
+    code: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut used = HashSet::new();
+    collect_uses(node_kinds, root, code, &mut Vec::new(), &mut used);
+
+    validate_unused_in(node_kinds, root, code, &used, diagnostics);
+}
+
+/// Warns about the unused names bound in `scope`. Nested sources are checked
+/// as scopes of their own.
+fn validate_unused_in(
+    node_kinds: &NodeKinds,
+    scope: Node,
+    code: &str,
+    used: &HashSet<(usize, usize)>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut definitions = Vec::new();
+    let mut names = HashSet::new();
+
+    for child in scope.named_children(&mut scope.walk()) {
+        collect_definitions(node_kinds, child, code, used, &mut definitions, diagnostics);
+    }
+
+    for target in definitions {
+        let name = &code[target.byte_range()];
+        let span = (target.start_byte(), target.end_byte());
+
+        // Later targets with the same name rebind the first one.
+        if names.insert(name) && !used.contains(&span) {
+            diagnostics.push(
+                Diagnostic::warning(format!("Warning: Unused variable `{}`.", name), span)
+                    .with_code(ErrorCode::UnusedVariable),
+            );
+        }
+    }
+}
+
+fn collect_definitions<'a>(
+    node_kinds: &NodeKinds,
+    node: Node<'a>,
+    code: &str,
+    used: &HashSet<(usize, usize)>,
+    definitions: &mut Vec<Node<'a>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if node.kind_id() == node_kinds.source {
+        validate_unused_in(node_kinds, node, code, used, diagnostics);
+        return;
+    }
+
+    if let Some(target) = assign_target(node_kinds, node) {
+        definitions.push(target);
+    }
+
+    for child in node.named_children(&mut node.walk()) {
+        collect_definitions(node_kinds, child, code, used, definitions, diagnostics);
+    }
+}
+
+/// Spans of the definitions that the identifiers under `node` refer to,
+/// leaving out the targets of assignments. `scopes` holds the bindings of the
+/// sources around `node`, innermost last, so that a plain name is resolved
+/// without searching each source again. Names after `.` are resolved with
+/// `definition_of`.
+fn collect_uses<'a>(
+    node_kinds: &NodeKinds,
+    node: Node,
+    code: &'a str,
+    scopes: &mut Vec<HashMap<&'a str, (usize, usize)>>,
+    used: &mut HashSet<(usize, usize)>,
+) {
+    if node.kind_id() == node_kinds.source || node.parent().is_none() {
+        let mut bindings = HashMap::new();
+        collect_bindings(node_kinds, node, code, &mut bindings);
+
+        scopes.push(bindings);
+
+        for child in node.named_children(&mut node.walk()) {
+            collect_uses(node_kinds, child, code, scopes, used);
+        }
+
+        scopes.pop();
+        return;
+    }
+
+    if node.kind_id() == node_kinds.identifier {
+        let is_member = node.parent().is_some_and(|parent| {
+            parent.kind_id() == node_kinds.member && parent.named_child(2) == Some(node)
+        });
+
+        let definition = if is_member {
+            definition_of(node_kinds, code, node)
+                .map(|definition| (definition.start_byte(), definition.end_byte()))
+        } else {
+            let name = &code[node.byte_range()];
+
+            scopes
+                .iter()
+                .rev()
+                .find_map(|bindings| bindings.get(name).copied())
+        };
+
+        used.extend(definition);
+        return;
+    }
+
+    let target = assign_target(node_kinds, node);
+
+    for child in node.named_children(&mut node.walk()) {
+        if Some(child) != target {
+            collect_uses(node_kinds, child, code, scopes, used);
+        }
+    }
+}
+
+/// First target of each name bound directly in `scope`, outside any nested
+/// source, as `definition_of` finds them.
+fn collect_bindings<'a>(
+    node_kinds: &NodeKinds,
+    scope: Node,
+    code: &'a str,
+    bindings: &mut HashMap<&'a str, (usize, usize)>,
+) {
+    for child in scope.named_children(&mut scope.walk()) {
+        if child.kind_id() == node_kinds.source {
+            continue;
+        }
+
+        if let Some(target) = assign_target(node_kinds, child) {
+            bindings
+                .entry(&code[target.byte_range()])
+                .or_insert((target.start_byte(), target.end_byte()));
+        }
+
+        collect_bindings(node_kinds, child, code, bindings);
+    }
+}
+
+fn assign_target<'a>(node_kinds: &NodeKinds, node: Node<'a>) -> Option<Node<'a>> {
+    if node.kind_id() != node_kinds.assign {
+        return None;
+    }
+
+    node.named_child(0)
+        .filter(|target| target.kind_id() == node_kinds.identifier)
+}
@@ -0,0 +1,285 @@
+use crate::{Diagnostic, ErrorCode, IntWidth, LineIndex, Mage, Severity, limit_diagnostics};
+
+#[cfg(feature = "lsp")]
+use crate::lsp_diagnostic;
+
+#[test]
+fn test_valid_program() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(mage.validate("a : 0d1 + b\na => f").unwrap(), vec![]);
+}
+
+#[test]
+fn test_unexpected_token() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.validate("a : 0d1 )").unwrap(),
+        vec![
+            Diagnostic::error("Error: Unexpected `)`.".to_string(), (8, 9))
+                .with_code(ErrorCode::UnexpectedSyntax)
+        ]
+    );
+}
+
+#[test]
+fn test_front_ends_agree() {
+    let code = "a : 0d1\nb : )";
+
+    let mut mage = Mage::new().unwrap();
+    let diagnostics = mage.validate(code).unwrap();
+    let line_index = LineIndex::new(code);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(
+        diagnostics[0].render(&line_index),
+        "2:3: Error: Unexpected `: )`."
+    );
+
+    #[cfg(feature = "lsp")]
+    {
+        let converted = lsp_diagnostic(&diagnostics[0], &line_index);
+
+        assert_eq!(converted.message, diagnostics[0].message);
+        assert_eq!(converted.range.start.line, 1);
+        assert_eq!(converted.range.start.character, 2);
+        assert_eq!(converted.range.end.character, 5);
+    }
+}
+
+#[test]
+fn test_unused_variable() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.validate("a : 0d1\nb : a").unwrap(),
+        vec![
+            Diagnostic::warning("Warning: Unused variable `b`.".to_string(), (8, 9))
+                .with_code(ErrorCode::UnusedVariable)
+        ]
+    );
+}
+
+#[test]
+fn test_variable_used_in_nested_source() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(mage.validate("a : 0d1\n{ a => f }").unwrap(), vec![]);
+}
+
+#[test]
+fn test_shadowed_variable_unused() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.validate("x : 0d1\nf : { x : 0d2\nx => print }\nf => print")
+            .unwrap(),
+        vec![
+            Diagnostic::warning("Warning: Unused variable `x`.".to_string(), (0, 1))
+                .with_code(ErrorCode::UnusedVariable)
+        ]
+    );
+}
+
+#[test]
+fn test_member_is_not_outer_use() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.validate("b : 0d1\na : {}\na.b => print").unwrap(),
+        vec![
+            Diagnostic::warning("Warning: Unused variable `b`.".to_string(), (0, 1))
+                .with_code(ErrorCode::UnusedVariable)
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_number_literal() {
+    let mut mage = Mage::new().unwrap();
+
+    let diagnostics = mage.validate("x : 0b12\nx => f").unwrap();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "Error: Invalid number literal `0b12`."
+    );
+    assert_eq!(diagnostics[0].span, (4, 8));
+    assert!(
+        diagnostics[0]
+            .fixes
+            .iter()
+            .any(|fix| fix.edits[0].replacement == "0d12")
+    );
+}
+
+#[test]
+fn test_number_literal_overflow() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.validate("x : 0d9_223_372_036_854_775_808\nx => f")
+            .unwrap(),
+        vec![
+            Diagnostic::error(
+                "Error: Number literal `0d9_223_372_036_854_775_808` does not fit in 64 bits."
+                    .to_string(),
+                (4, 31)
+            )
+            .with_code(ErrorCode::NumberOutOfRange)
+        ]
+    );
+    assert_eq!(
+        mage.validate("x : 0x7FFFFFFFFFFFFFFF\nx => f").unwrap(),
+        vec![]
+    );
+}
+
+#[test]
+fn test_int_width_validation() {
+    let mut mage = Mage::new().unwrap();
+    let code = "x : 0d3_000_000_000\nx => f";
+
+    assert_eq!(mage.validate(code).unwrap(), vec![]);
+
+    mage.int_width = IntWidth::I32;
+
+    assert_eq!(mage.validate(code).unwrap().len(), 1);
+}
+
+#[test]
+fn test_negated_literal_validation() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(mage.validate("x : -0d5\nx => f").unwrap(), vec![]);
+
+    mage.int_width = IntWidth::I32;
+
+    assert_eq!(mage.validate("x : -0x8000_0000\nx => f").unwrap(), vec![]);
+    assert_eq!(mage.validate("x : 0x8000_0000\nx => f").unwrap().len(), 1);
+}
+
+#[test]
+fn test_definition_without_expression() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.validate("a : 0d1 x :").unwrap(),
+        vec![
+            Diagnostic::error(
+                "Error: Definition of `x` has no expression.".to_string(),
+                (8, 11)
+            )
+            .with_code(ErrorCode::DefinitionWithoutExpression)
+        ]
+    );
+    assert_eq!(
+        mage.validate("{ x = }").unwrap(),
+        vec![
+            Diagnostic::error(
+                "Error: Definition of `x` has no expression.".to_string(),
+                (2, 5)
+            )
+            .with_code(ErrorCode::DefinitionWithoutExpression)
+        ]
+    );
+}
+
+#[test]
+fn test_definition_chains() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(mage.validate("a : b : 0d5\na + b => f").unwrap(), vec![]);
+    assert_eq!(
+        mage.validate("a : b = 0d5\na + b => f").unwrap(),
+        vec![
+            Diagnostic::error(
+                "Error: Definition chain mixes constant `:` and variable `=` bindings.".to_string(),
+                (0, 11)
+            )
+            .with_code(ErrorCode::MixedDefinitionChain)
+        ]
+    );
+}
+
+#[test]
+fn test_division_by_constant_zero() {
+    let mut mage = Mage::new().unwrap();
+
+    assert_eq!(
+        mage.validate("x : 0d0\ny : a / x\ny => f").unwrap(),
+        vec![
+            Diagnostic::error("Error: Division by zero.".to_string(), (12, 17))
+                .with_code(ErrorCode::DivisionByZero)
+        ]
+    );
+    assert_eq!(
+        mage.validate("x : 0d2 - 0d2\ny : a % (x * 0d3)\ny => f")
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(mage.validate("y : a / x\ny => f").unwrap(), vec![]);
+    assert_eq!(mage.validate("x = 0d0\ny : a / x\ny => f").unwrap(), vec![]);
+}
+
+#[test]
+fn test_diagnostic_codes() {
+    let mut mage = Mage::new().unwrap();
+
+    let diagnostics = mage.validate("x : 0b12\nx => f").unwrap();
+
+    assert_eq!(diagnostics[0].code, Some(ErrorCode::InvalidNumber));
+    assert!(
+        serde_json::to_string(&diagnostics)
+            .unwrap()
+            .contains(r#""code":"E0004""#)
+    );
+}
+
+#[test]
+fn test_invalid_string_escape() {
+    let mut mage = Mage::new().unwrap();
+    let code = "s : \"a\\uD800\"\ns => print";
+
+    let expected = vec![
+        Diagnostic::error(
+            "Error: Invalid escape `\\uD800` in string literal.".to_string(),
+            (6, 12),
+        )
+        .with_code(ErrorCode::InvalidEscape),
+    ];
+
+    assert_eq!(mage.validate(code).unwrap(), expected);
+    assert!(mage.accepts(code).is_err());
+    assert_eq!(
+        mage.flatten(code).unwrap_err().span(),
+        Some(expected[0].span)
+    );
+}
+
+#[test]
+fn test_limit_diagnostics() {
+    let mut mage = Mage::new().unwrap();
+
+    let code = "a : 0b12 + 0b13 + 0b14 + 0b15 + 0b16\na => f";
+    let diagnostics = limit_diagnostics(mage.validate(code).unwrap(), 2);
+
+    assert_eq!(diagnostics.len(), 3);
+    assert_eq!(
+        diagnostics[1].message,
+        "Error: Invalid number literal `0b13`."
+    );
+    assert_eq!(
+        diagnostics[2],
+        Diagnostic::information(
+            "Information: 3 more diagnostics suppressed.".to_string(),
+            (18, 22)
+        )
+    );
+
+    assert_eq!(limit_diagnostics(mage.validate(code).unwrap(), 5).len(), 5);
+}
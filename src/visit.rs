@@ -0,0 +1,44 @@
+use crate::{FlatExpression, FlatIdentifier, FlatNumber, FlatRoot, FlatSource, FlatString};
+
+/// Callbacks for analyses over a flattened program. Every method has an empty
+/// default, except `visit_source`, which walks the source's expressions and
+/// identifiers.
+pub trait Visitor {
+    fn visit_source(&mut self, source: &FlatSource) {
+        walk_source(source, self);
+    }
+
+    fn visit_expression(&mut self, _expression: &FlatExpression) {}
+
+    fn visit_identifier(&mut self, _identifier: &FlatIdentifier) {}
+
+    fn visit_number(&mut self, _number: &FlatNumber) {}
+
+    fn visit_string(&mut self, _string: &FlatString) {}
+}
+
+/// Visits every source, then every number and string of `root`, each exactly
+/// once and in index order.
+pub fn walk_root<V: Visitor + ?Sized>(root: &FlatRoot, visitor: &mut V) {
+    for source in &root.sources {
+        visitor.visit_source(source);
+    }
+
+    for number in &root.numbers {
+        visitor.visit_number(number);
+    }
+
+    for string in &root.strings {
+        visitor.visit_string(string);
+    }
+}
+
+pub fn walk_source<V: Visitor + ?Sized>(source: &FlatSource, visitor: &mut V) {
+    for expression in &source.expressions {
+        visitor.visit_expression(expression);
+    }
+
+    for identifier in &source.identifiers {
+        visitor.visit_identifier(identifier);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{FlatExpression, FlatNumber, Mage, Visitor, walk_root};
+
+#[derive(Default)]
+struct DefinitionCounter {
+    definitions: usize,
+    numbers: usize,
+}
+
+impl Visitor for DefinitionCounter {
+    fn visit_expression(&mut self, expression: &FlatExpression) {
+        if let FlatExpression::Assign(_) = expression {
+            self.definitions += 1;
+        }
+    }
+
+    fn visit_number(&mut self, _: &FlatNumber) {
+        self.numbers += 1;
+    }
+}
+
+#[test]
+fn test_count_definitions() {
+    let root = Mage::new()
+        .unwrap()
+        .flatten("a : 0d1\nb = a * 0d2\n{ c : b + 0d1 }\nc => print")
+        .unwrap();
+
+    let mut counter = DefinitionCounter::default();
+    walk_root(&root, &mut counter);
+
+    assert_eq!(counter.definitions, 3);
+    assert_eq!(counter.numbers, 2);
+}
@@ -0,0 +1,68 @@
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Quiet period after a change before it is reported, unless configured
+/// otherwise.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Notices changes to a file by polling its modification time, length and a
+/// hash of its contents, so no platform file watching is needed and an edit
+/// keeping both the length and a coarse modification time is still seen. A change is only reported once the
+/// file has stayed the same for the debounce period, so that an editor saving
+/// several times in a row causes a single run.
+pub struct Watcher {
+    path: PathBuf,
+    debounce: Duration,
+    seen: Option<Stamp>,
+    changed: Option<Instant>,
+}
+
+impl Watcher {
+    pub fn new(path: &Path, debounce: Duration) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            debounce,
+            seen: stamp(path),
+            changed: None,
+        }
+    }
+
+    /// Whether the file changed and then settled since the last time this
+    /// returned `true`.
+    pub fn poll(&mut self) -> bool {
+        let current = stamp(&self.path);
+
+        if current != self.seen {
+            self.seen = current;
+            self.changed = Some(Instant::now());
+            return false;
+        }
+
+        match self.changed {
+            Some(changed) if changed.elapsed() >= self.debounce => {
+                self.changed = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Modification time, length and content hash of a file.
+type Stamp = (SystemTime, u64, u64);
+
+/// Missing or unreadable files have no stamp, so their removal and return
+/// count as changes too.
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    let contents = fs::read(path).ok()?;
+
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+
+    Some((metadata.modified().ok()?, metadata.len(), hasher.finish()))
+}
@@ -0,0 +1,60 @@
+use std::{env, fs, path::Path, thread, time::Duration};
+
+use crate::Watcher;
+
+/// Writes `text` to `path` keeping its modification time, so that only the
+/// contents tell the change apart.
+fn rewrite(path: &Path, text: &str) {
+    let modified = fs::metadata(path).unwrap().modified().unwrap();
+
+    fs::write(path, text).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+#[test]
+fn test_changes_are_debounced() {
+    let path = env::temp_dir().join(format!("mage-{}-watch.mage", std::process::id()));
+    fs::write(&path, "a : 0d1\n").unwrap();
+
+    let debounce = Duration::from_millis(100);
+    let mut watcher = Watcher::new(&path, debounce);
+
+    assert!(!watcher.poll());
+
+    rewrite(&path, "a : 0d2\n");
+    assert!(!watcher.poll());
+
+    rewrite(&path, "a : 0d3\n");
+    assert!(!watcher.poll());
+
+    thread::sleep(debounce * 2);
+
+    let runs = (0..5).filter(|_| watcher.poll()).count();
+
+    assert_eq!(runs, 1);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_same_length_change() {
+    let path = env::temp_dir().join(format!("mage-{}-same-length.mage", std::process::id()));
+    fs::write(&path, "a : 0d1\n").unwrap();
+
+    let mut watcher = Watcher::new(&path, Duration::ZERO);
+
+    assert!(!watcher.poll());
+
+    rewrite(&path, "a : 0d2\n");
+
+    assert!(!watcher.poll());
+    assert!(watcher.poll());
+    assert!(!watcher.poll());
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,287 @@
+use std::{
+    env, fs,
+    io::Write,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+fn mage(arguments: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mage-rs"))
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+/// File or directory under the temporary directory, removed when dropped so
+/// that test runs leave nothing behind, even when they fail.
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(name: &str) -> Self {
+        Self(env::temp_dir().join(format!("mage-{}-{}", std::process::id(), name)))
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = fs::remove_dir_all(&self.0);
+        } else {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+}
+
+fn temp_file(name: &str, contents: &str) -> TempPath {
+    let path = TempPath::new(name);
+    fs::write(&*path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_emit_tree_on_error() {
+    let output = mage(&["run", "--emit-tree"], "a : 0d1 )\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("(source_file (assign"));
+    assert!(stderr.contains("(ERROR)"));
+}
+
+#[test]
+fn test_no_tree_without_flag() {
+    let output = mage(&["run"], "a : 0d1 )\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!output.status.success());
+    assert!(!stderr.contains("(source_file"));
+}
+
+#[test]
+fn test_check_clean_file() {
+    let path = temp_file("clean.mage", "a : 0d1\na => print\n");
+    let output = mage(&["check", path.to_str().unwrap()], "");
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_check_malformed_file() {
+    let path = temp_file("malformed.mage", "a : 0d1\nb : )\n");
+    let output = mage(&["check", path.to_str().unwrap()], "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("malformed.mage:2:3: Error: Unexpected `: )`."));
+}
+
+#[test]
+fn test_check_missing_file() {
+    let path = TempPath::new("missing.mage");
+    let output = mage(&["check", path.to_str().unwrap()], "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with(&format!("{}: ", path.display())));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_build_missing_directory() {
+    let path = TempPath::new("missing");
+    let output = mage(&["build", path.to_str().unwrap()], "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with("Error: Unable to read directory"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_explain() {
+    let output = mage(&["explain", "E0004"], "");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.contains("0x"));
+
+    let output = mage(&["explain", "E9999"], "");
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_fmt_check() {
+    let path = temp_file("messy.mage", "a:0d1\na=>print\n");
+    let output = mage(&["fmt", "--check", path.to_str().unwrap()], "");
+
+    assert!(!output.status.success());
+
+    let output = mage(&["fmt", path.to_str().unwrap()], "");
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&*path).unwrap(),
+        "a : 0d1\na => print\n"
+    );
+
+    let output = mage(&["fmt", "--check", path.to_str().unwrap()], "");
+
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_fmt_missing_file() {
+    let path = TempPath::new("unformatted.mage");
+    let output = mage(&["fmt", path.to_str().unwrap()], "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with(&format!("{}: ", path.display())));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_time_stages() {
+    let output = mage(&["run", "--time", "--stage", "validate"], "a : 0d1\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(output.status.success());
+    assert!(stderr.contains("parse: "));
+    assert!(stderr.contains("validate: "));
+    assert!(!stderr.contains("flatten: "));
+}
+
+#[test]
+fn test_check_max_errors() {
+    let path = temp_file("broken.mage", "a : 0b12 + 0b13 + 0b14 + 0b15\na => f\n");
+    let output = mage(&["--max-errors", "1", "check", path.to_str().unwrap()], "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(stderr.lines().count(), 2);
+    assert!(stderr.contains("Information: 3 more diagnostics suppressed."));
+}
+
+#[test]
+fn test_run_input_resolution() {
+    let stdin = mage(&["--output", "json", "run", "-"], "a : 0d7\n");
+
+    assert!(stdin.status.success());
+    assert!(String::from_utf8(stdin.stdout).unwrap().contains("0d7"));
+
+    let directory = TempPath::new("dash");
+    fs::create_dir_all(&*directory).unwrap();
+    fs::write(directory.join("-"), "b : 0d9\n").unwrap();
+
+    let file = Command::new(env!("CARGO_BIN_EXE_mage-rs"))
+        .args(["--output", "json", "run", "./-"])
+        .current_dir(&*directory)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(file.status.success());
+    assert!(String::from_utf8(file.stdout).unwrap().contains("0d9"));
+
+    let uri = format!("file://{}", directory.join("-").display());
+    let output = mage(&["--output", "json", "run", &uri], "");
+
+    assert!(String::from_utf8(output.stdout).unwrap().contains("0d9"));
+
+    fs::write(directory.join("a b.mage"), "c : 0d11\n").unwrap();
+
+    let uri = format!("file://localhost{}/a%20b.mage", directory.display());
+    let output = mage(&["--output", "json", "run", &uri], "");
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("0d11"));
+}
+
+#[test]
+fn test_run_missing_file() {
+    let path = TempPath::new("missing.mage");
+    let output = mage(&["run", path.to_str().unwrap()], "");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with(&format!("{}: ", path.display())));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_run_watch_stdin() {
+    let output = mage(&["run", "--watch", "-"], "a : 0d1\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with("Error: --watch needs a file"));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_run_strict() {
+    let output = mage(&["run"], "a : 0d1\n");
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    let output = mage(&["run", "--strict"], "a : 0d1\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("Warning: Unused variable `a`."));
+    assert!(stderr.contains("Error: Validation failed."));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_run_no_validate() {
+    let output = mage(&["--int-width", "i32", "run"], "a : 0d3000000000\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("1:5: Error:"));
+    assert!(!stderr.contains("panicked"));
+
+    let output = mage(
+        &["--int-width", "i32", "run", "--no-validate"],
+        "a : 0d3000000000\n",
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("does not fit in 32 bits"));
+    assert!(!stderr.contains("panicked"));
+
+    let output = mage(
+        &["--int-width", "i32", "run", "--no-validate"],
+        "a : 0d2000000000\n",
+    );
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
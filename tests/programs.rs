@@ -0,0 +1,57 @@
+//! Golden tests for the programs in `tests/programs`. Each `.mage` file is
+//! paired with the `.json` root it flattens to.
+
+use std::{fs, path::Path};
+
+#[cfg(feature = "jit")]
+use mage_rs::{Error, compile_root};
+use mage_rs::{Mage, MagePool};
+
+#[test]
+fn test_programs() {
+    let mut mage = Mage::new().unwrap();
+    let pool = MagePool::new();
+
+    let mut paths: Vec<_> =
+        fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "mage")
+            })
+            .collect();
+    paths.sort();
+
+    assert!(paths.len() >= 10);
+
+    for path in paths {
+        let text = fs::read_to_string(&path).unwrap();
+        let expected: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path.with_extension("json")).unwrap())
+                .unwrap();
+
+        let root = mage.flatten(&text).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&root).unwrap(),
+            expected,
+            "{}",
+            path.display()
+        );
+        assert_eq!(pool.flatten(&text).unwrap(), root, "{}", path.display());
+        assert_eq!(mage.accepts(&text), Ok(()), "{}", path.display());
+
+        // The JIT does not generate code yet, so it only has to get past its
+        // checks.
+        #[cfg(feature = "jit")]
+        assert_eq!(
+            compile_root(root),
+            Err(Error::JitError(
+                "Error: JIT compilation is not implemented.".to_string()
+            )),
+            "{}",
+            path.display()
+        );
+    }
+}